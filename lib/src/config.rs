@@ -1,5 +1,9 @@
-use crate::{creeps::Role, error::Res};
-use screeps::constants::Part;
+use crate::{
+    creeps::{BodyTemplate, EquipEntry, Role},
+    error::{ErrorKind, Res},
+};
+use screeps::ResourceType;
+use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
 use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
 
@@ -7,8 +11,40 @@ use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
 pub struct ConfigMemory {
     #[serde(default)]
     pub roles_to_spawn: HashMap<String, Vec<(Role, String)>>,
+    #[serde(default, deserialize_with = "deserialize_equip")]
+    pub equip: HashMap<String, BodyTemplate>,
+    /// Rooms whose creep counts are worked out from the room itself, with
+    /// `roles_to_spawn` only overriding individual roles.
     #[serde(default)]
-    pub equip: HashMap<String, Vec<Part>>,
+    pub auto_population: Vec<String>,
+    /// The `equip` entry used for each role in `auto_population` rooms and
+    /// for creeps requested by the empire.
+    #[serde(default)]
+    pub role_equip: HashMap<Role, String>,
+    /// Rooms to claim and build up as new colonies.
+    #[serde(default)]
+    pub expansion_targets: Vec<String>,
+    /// Unreserved rooms we mine from.
+    #[serde(default)]
+    pub remote_rooms: Vec<String>,
+    /// Compounds every room with labs keeps in stock, and how much of each,
+    /// made in this order.
+    #[serde(default)]
+    pub reaction_targets: Vec<(ResourceType, u32)>,
+    /// Compounds creeps of each role are boosted with before they go to
+    /// work, where their room has them in stock.
+    #[serde(default)]
+    pub role_boosts: HashMap<Role, Vec<ResourceType>>,
+}
+
+/// Reads `equip` entries in either form, so configs from before templates
+/// keep working. They're written back as templates.
+fn deserialize_equip<'de, D>(deserializer: D) -> Result<HashMap<String, BodyTemplate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = HashMap::<String, EquipEntry>::deserialize(deserializer)?;
+    Ok(entries.into_iter().map(|(k, v)| (k, v.into())).collect())
 }
 
 js_serializable!(ConfigMemory);
@@ -24,7 +60,7 @@ impl Config {
         let memory = Some(
             screeps::memory::root()
                 .get("config")?
-                .ok_or("undefined or null config memory")?,
+                .ok_or_else(|| ErrorKind::MissingMemory("config".to_string()))?,
         );
         Ok(Self { memory })
     }
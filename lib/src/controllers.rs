@@ -1,3 +1,5 @@
+mod fault_controller;
 mod memory_controller;
 
+pub use fault_controller::{Failures, FaultController, FaultMemory};
 pub use memory_controller::MemoryController;
@@ -0,0 +1,153 @@
+use crate::error::Res;
+use log::*;
+use std::collections::HashMap;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// Consecutive failing ticks before an entity is quarantined.
+const QUARANTINE_AFTER: u32 = 5;
+/// How long a quarantined entity is skipped for.
+const QUARANTINE_TICKS: u32 = 100;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Failures {
+    /// Consecutive ticks the entity has failed in.
+    pub count: u32,
+    /// The last tick it failed in.
+    pub last: u32,
+}
+
+js_serializable!(Failures);
+js_deserializable!(Failures);
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FaultMemory {
+    #[serde(default)]
+    pub failures: HashMap<String, Failures>,
+    /// Quarantined entities and the tick they're let out.
+    #[serde(default)]
+    pub quarantine: HashMap<String, u32>,
+}
+
+js_serializable!(FaultMemory);
+js_deserializable!(FaultMemory);
+
+/// Runs each creep, room and so on separately, so that one of them failing
+/// doesn't stop the rest of the tick.
+pub struct FaultController {
+    memory: Option<FaultMemory>,
+    ran: u32,
+    failed: u32,
+    skipped: u32,
+    /// Failures this tick by error kind.
+    kinds: HashMap<&'static str, u32>,
+}
+
+impl Drop for FaultController {
+    fn drop(&mut self) {
+        screeps::memory::root().set("faults", self.memory.take());
+    }
+}
+
+impl FaultController {
+    pub fn new() -> Res<Self> {
+        let mut memory: FaultMemory = screeps::memory::root().get("faults")?.unwrap_or_default();
+
+        let time = screeps::game::time();
+        let released = memory
+            .quarantine
+            .iter()
+            .filter(|(_, until)| **until <= time)
+            .map(|(entity, _)| entity.clone())
+            .collect::<Vec<_>>();
+        for entity in released {
+            info!("Releasing {} from quarantine", entity);
+            memory.quarantine.remove(&entity);
+            memory.failures.remove(&entity);
+        }
+        // Anything that didn't fail last tick has recovered
+        memory.failures.retain(|_, f| f.last + 1 >= time);
+
+        Ok(Self {
+            memory: Some(memory),
+            ran: 0,
+            failed: 0,
+            skipped: 0,
+            kinds: HashMap::new(),
+        })
+    }
+
+    pub fn memory(&self) -> &FaultMemory {
+        self.memory
+            .as_ref()
+            .expect("faults.memory is not populated")
+    }
+
+    pub fn memory_mut(&mut self) -> &mut FaultMemory {
+        self.memory
+            .as_mut()
+            .expect("faults.memory is not populated")
+    }
+
+    pub fn is_quarantined(&self, entity: &str) -> bool {
+        self.memory().quarantine.contains_key(entity)
+    }
+
+    /// Runs `f` for `entity` unless it's quarantined, recording whether it
+    /// failed. Errors are logged and swallowed.
+    pub fn guard<T, F: FnOnce() -> Res<T>>(&mut self, entity: &str, f: F) -> Option<T> {
+        if self.is_quarantined(entity) {
+            self.skipped += 1;
+            return None;
+        }
+
+        self.ran += 1;
+        match f() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.failed += 1;
+                *self.kinds.entry(e.kind().name()).or_insert(0) += 1;
+                warn!("{} failed: {}", entity, e);
+
+                let time = screeps::game::time();
+                let count = {
+                    let failures = self
+                        .memory_mut()
+                        .failures
+                        .entry(entity.to_string())
+                        .or_default();
+                    if failures.count == 0 || failures.last < time {
+                        failures.count += 1;
+                        failures.last = time;
+                    }
+                    failures.count
+                };
+                if count >= QUARANTINE_AFTER {
+                    warn!("Quarantining {} after {} failing ticks", entity, count);
+                    self.memory_mut()
+                        .quarantine
+                        .insert(entity.to_string(), time + QUARANTINE_TICKS);
+                }
+                None
+            }
+        }
+    }
+
+    /// Logs how this tick went.
+    pub fn summary(&self) {
+        if self.failed > 0 || self.skipped > 0 {
+            let mut kinds = self
+                .kinds
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect::<Vec<_>>();
+            kinds.sort();
+            info!(
+                "{} ran, {} failed ({}), {} quarantined",
+                self.ran,
+                self.failed,
+                kinds.join(", "),
+                self.skipped
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::Res;
+use crate::error::{ErrorKind, Res};
 use log::*;
 use std::collections::HashSet;
 
@@ -21,7 +21,7 @@ impl MemoryController {
         let creeps_active: HashSet<String> = screeps::game::creeps::keys().into_iter().collect();
         let creeps_memory = screeps::memory::root()
             .dict("creeps")?
-            .ok_or("creeps does not exist in Memory")?;
+            .ok_or_else(|| ErrorKind::MissingMemory("creeps".to_string()))?;
 
         for mem_name in creeps_memory.keys() {
             if !creeps_active.contains(&mem_name) {
@@ -37,7 +37,7 @@ impl MemoryController {
         let spawns_active: HashSet<String> = screeps::game::spawns::keys().into_iter().collect();
         let spawns_memory = screeps::memory::root()
             .dict("spawns")?
-            .ok_or("spawns does not exist in Memory")?;
+            .ok_or_else(|| ErrorKind::MissingMemory("spawns".to_string()))?;
 
         for mem_name in spawns_memory.keys() {
             if !spawns_active.contains(&mem_name) {
@@ -53,7 +53,7 @@ impl MemoryController {
         let flags_active: HashSet<String> = screeps::game::flags::keys().into_iter().collect();
         let flags_memory = screeps::memory::root()
             .dict("flags")?
-            .ok_or("flags does not exist in Memory")?;
+            .ok_or_else(|| ErrorKind::MissingMemory("flags".to_string()))?;
 
         for mem_name in flags_memory.keys() {
             if !flags_active.contains(&mem_name) {
@@ -1,11 +1,15 @@
-use self::actions::Action;
-use crate::error::Res;
+pub use self::actions::{Action, BOOST_ENERGY, BOOST_MINERAL};
+use crate::error::{ErrorKind, Res};
+pub use body::{body_cost, BodyTemplate, EquipEntry, Movement};
 pub use roles::Role;
-use screeps::Creep as ScreepsCreep;
+use screeps::{memory::MemoryReference, Creep as ScreepsCreep, HasPosition, Position};
 use std::collections::VecDeque;
-use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+use stdweb::{
+    __js_serializable_boilerplate, js, js_deserializable, js_serializable, unstable::TryInto,
+};
 
 mod actions;
+mod body;
 mod roles;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -15,21 +19,42 @@ pub struct CreepMemory {
     pub role: Role,
     #[serde(default)]
     pub actions: VecDeque<Action>,
+    #[serde(default)]
+    pub spawned: u32,
+    #[serde(default)]
+    pub cost: u32,
+    /// The `equip` entry this creep was spawned from.
+    #[serde(default)]
+    pub equip: String,
+    /// Where the creep does its work, if it has a fixed work site.
+    #[serde(default)]
+    pub work_pos: Option<Position>,
+    #[serde(default)]
+    pub replacement_requested: bool,
+    /// The dying creep this one takes over from once it gets to the work
+    /// site.
+    #[serde(default)]
+    pub replaces: Option<String>,
 }
 
 js_serializable!(CreepMemory);
 js_deserializable!(CreepMemory);
 
+impl CreepMemory {
+    /// Converts to a JS object through the same serde representation that
+    /// `Creep::new` reads back.
+    pub fn to_memory_reference(&self) -> Res<MemoryReference> {
+        let value = js! {
+            return @{self.clone()};
+        };
+        Ok(value.try_into()?)
+    }
+}
+
 pub struct Creep {
     obj: ScreepsCreep,
     memory: Option<CreepMemory>,
-}
-
-impl Drop for Creep {
-    fn drop(&mut self) {
-        screeps::memory::root()
-            .path_set(&format!("creeps.{}", self.obj.name()), self.memory.take());
-    }
+    dirty: bool,
 }
 
 impl Creep {
@@ -37,9 +62,21 @@ impl Creep {
         let memory = Some(
             screeps::memory::root()
                 .get_path(&format!("creeps.{}", obj.name()))?
-                .ok_or_else(|| format!("undefined or null creep memory for {}", obj.name()))?,
+                .ok_or_else(|| ErrorKind::MissingMemory(format!("creeps.{}", obj.name())))?,
         );
-        Ok(Self { obj, memory })
+        Ok(Self {
+            obj,
+            memory,
+            dirty: false,
+        })
+    }
+
+    /// Writes the memory back if it was changed.
+    pub fn save(mut self) {
+        if self.dirty {
+            screeps::memory::root()
+                .path_set(&format!("creeps.{}", self.obj.name()), self.memory.take());
+        }
     }
 
     pub fn obj(&self) -> &ScreepsCreep {
@@ -51,12 +88,31 @@ impl Creep {
     }
 
     pub fn memory_mut(&mut self) -> &mut CreepMemory {
+        self.dirty = true;
         self.memory.as_mut().expect("creep.memory is not populated")
     }
 
+    /// Where this creep works: its saved work site, else the place of its
+    /// current action, else where it stands.
+    pub fn work_site(&self) -> Position {
+        let memory = self.memory();
+        memory
+            .work_pos
+            .or_else(|| memory.actions.front().and_then(|a| a.pos()))
+            .unwrap_or_else(|| self.obj.pos())
+    }
+
     pub fn run(&mut self) -> Res<()> {
         if let Some(action) = self.memory().actions.front().cloned() {
-            action.run(self)?;
+            // Drop an action that fails, e.g. because its target is gone,
+            // so the creep can get on with the next one
+            if let Err(e) = action.run(self) {
+                let actions = &mut self.memory_mut().actions;
+                if actions.front() == Some(&action) {
+                    actions.pop_front();
+                }
+                return Err(e);
+            }
         }
 
         Ok(())
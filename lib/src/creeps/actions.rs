@@ -1,10 +1,15 @@
 #![allow(unused_variables)]
-use super::Creep;
-use crate::{error::Res, Id};
+use super::{Creep, Role};
+use crate::{
+    error::{ErrorKind, Res},
+    planner::fortify_target,
+    Id,
+};
 use screeps::{
-    game::get_object_erased, traits::IntoExpectedType, ConstructionSite, Creep as ScreepsCreep,
-    HasPosition, MoveToOptions, Position, Resource, ResourceType, RoomName, Source, Structure,
-    StructureController, StructureLab, StructureSpawn,
+    find, game::get_object_erased, traits::IntoExpectedType, CanStoreEnergy, ConstructionSite,
+    Creep as ScreepsCreep, HasPosition, MoveToOptions, Position, Resource, ResourceType,
+    ReturnCode, RoomName, RoomObjectProperties, Source, Structure, StructureController,
+    StructureLab, StructureSpawn,
 };
 use stdweb::{Reference, __js_serializable_boilerplate, js_deserializable, js_serializable};
 
@@ -14,10 +19,10 @@ const RANGE_WITHDRAW: u32 = 1;
 const RANGE_HARVEST: u32 = 1;
 const RANGE_BUILD: u32 = 3;
 const RANGE_REPAIR: u32 = 3;
-//const RANGE_HEAL: u32 = 1;
-//const RANGE_HEAL_RANGED: u32 = 3;
-//const RANGE_ATTACK_MELEE: u32 = 1;
-//const RANGE_ATTACK_RANGED: u32 = 3;
+const RANGE_HEAL: u32 = 1;
+const RANGE_HEAL_RANGED: u32 = 3;
+const RANGE_ATTACK_MELEE: u32 = 1;
+const RANGE_ATTACK_RANGED: u32 = 3;
 const RANGE_CONTROLLER_ATTACK: u32 = 1;
 const RANGE_CONTROLLER_CLAIM: u32 = 1;
 const RANGE_CONTROLLER_RESERVE: u32 = 1;
@@ -25,6 +30,10 @@ const RANGE_CONTROLLER_UPGRADE: u32 = 3;
 const RANGE_BOOST: u32 = 1;
 const RANGE_RENEW: u32 = 1;
 const RANGE_RECYCLE: u32 = 1;
+/// Compound a lab spends boosting a single body part.
+pub const BOOST_MINERAL: u32 = 30;
+/// Energy a lab spends boosting a single body part.
+pub const BOOST_ENERGY: u32 = 20;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 // TODO: GoToWithOptions? For avoiding certain spots?
@@ -102,6 +111,7 @@ pub enum Action {
     AttackRangedMass,
     GetBoosted {
         lab_id: Id,
+        compound: ResourceType,
     },
     GetRenewed {
         spawn_id: Id,
@@ -115,6 +125,47 @@ js_serializable!(Action);
 js_deserializable!(Action);
 
 impl Action {
+    /// The id of the object this action works on, if any.
+    pub fn target_id(&self) -> Option<&Id> {
+        use Action::*;
+        match self {
+            GoTo { .. } | GoToRoom { .. } | GoToRanged { .. } | AttackRangedMass => None,
+            TransferAll { target_id, .. }
+            | TransferAmount { target_id, .. }
+            | WithdrawAll { target_id, .. }
+            | WithdrawAmount { target_id, .. }
+            | Pickup { target_id }
+            | Harvest { target_id }
+            | Dismantle { target_id }
+            | Repair { target_id }
+            | Fortify { target_id }
+            | ControllerAttack { target_id }
+            | ControllerClaim { target_id }
+            | ControllerUpgrade { target_id }
+            | ControllerReserve { target_id }
+            | Heal { target_id }
+            | HealRanged { target_id }
+            | AttackMelee { target_id }
+            | AttackRanged { target_id } => Some(target_id),
+            Build { site_id } => Some(site_id),
+            GetBoosted { lab_id, .. } => Some(lab_id),
+            GetRenewed { spawn_id } | GetRecycled { spawn_id } => Some(spawn_id),
+        }
+    }
+
+    /// Where this action takes place, looking the target up if needed.
+    pub fn pos(&self) -> Option<Position> {
+        use Action::*;
+        match self {
+            GoTo { pos } | GoToRanged { pos, .. } => Some(*pos),
+            GoToRoom { room_id } => Some(Position::new(25, 25, *room_id)),
+            _ => self
+                .target_id()
+                .and_then(|id| get_object_erased(id))
+                .map(|obj| obj.pos()),
+        }
+    }
+
     pub fn run(&self, creep: &mut Creep) -> Res<()> {
         use Action::*;
         match self {
@@ -198,8 +249,8 @@ impl Action {
             AttackRangedMass => {
                 attack_ranged_mass(creep)?;
             }
-            GetBoosted { lab_id } => {
-                get_boosted(creep, lab_id)?;
+            GetBoosted { lab_id, compound } => {
+                get_boosted(creep, lab_id, *compound)?;
             }
             GetRenewed { spawn_id } => {
                 get_renewed(creep, spawn_id)?;
@@ -212,7 +263,9 @@ impl Action {
     }
 }
 
-fn prepend_go_to_if_far(creep: &mut Creep, pos: Position, range: u32) -> Res<()> {
+/// Whether the creep is in range of `pos`. If it isn't, has it go there
+/// first, so the action is only tried again once it's arrived.
+fn prepend_go_to_if_far(creep: &mut Creep, pos: Position, range: u32) -> Res<bool> {
     // If target is not in range, order a GoTo first
     if !creep.obj.pos().in_range_to(&pos, range) {
         creep
@@ -221,22 +274,25 @@ fn prepend_go_to_if_far(creep: &mut Creep, pos: Position, range: u32) -> Res<()>
             .push_front(Action::GoToRanged { pos, range });
         // and do it
         go_to_ranged(creep, pos, range)?;
+        return Ok(false);
     }
 
-    Ok(())
+    Ok(true)
 }
 
 fn go_to(creep: &mut Creep, pos: Position) -> Res<()> {
-    creep.obj.move_to(&pos);
+    let ret = creep.obj.move_to(&pos);
 
     // When done, remove the action
     if creep.obj.pos().is_equal_to(&pos) {
         creep.memory_mut().actions.pop_front();
     }
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    // Tired creeps simply move on next tick
+    match ret {
+        ReturnCode::Tired => Ok(()),
+        ret => check(ret),
+    }
 }
 
 fn go_to_room(creep: &mut Creep, room_id: RoomName) -> Res<()> {
@@ -246,16 +302,18 @@ fn go_to_room(creep: &mut Creep, room_id: RoomName) -> Res<()> {
 
 fn go_to_ranged(creep: &mut Creep, pos: Position, range: u32) -> Res<()> {
     let options = MoveToOptions::new().range(range);
-    creep.obj.move_to_with_options(&pos, options);
+    let ret = creep.obj.move_to_with_options(&pos, options);
 
     // When done, remove the action
     if creep.obj.pos().in_range_to(&pos, range) {
         creep.memory_mut().actions.pop_front();
     }
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    // Tired creeps simply move on next tick
+    match ret {
+        ReturnCode::Tired => Ok(()),
+        ret => check(ret),
+    }
 }
 
 fn transfer_all(creep: &mut Creep, target_id: &Id, resource: ResourceType) -> Res<()> {
@@ -263,17 +321,21 @@ fn transfer_all(creep: &mut Creep, target_id: &Id, resource: ResourceType) -> Re
     let target_pos = target.pos();
     let target = target
         .as_transferable()
-        .ok_or_else(|| format!("{} is not transferable", target_id))?;
+        .ok_or_else(|| ErrorKind::WrongObjectType {
+            id: target_id.clone(),
+            expected: "transferable",
+        })?;
 
-    prepend_go_to_if_far(creep, target_pos, RANGE_TRANSFER)?;
-    creep.obj.transfer_all(target, resource);
+    if !prepend_go_to_if_far(creep, target_pos, RANGE_TRANSFER)? {
+        return Ok(());
+    }
+    let ret = creep.obj.transfer_all(target, resource);
 
     creep.memory_mut().actions.pop_front();
 
     // FIXME: Creeps aren't handled
-    // FIXME: Check if it worked
 
-    Ok(())
+    check(ret)
 }
 
 fn transfer_amount(
@@ -286,17 +348,21 @@ fn transfer_amount(
     let target_pos = target.pos();
     let target = target
         .as_transferable()
-        .ok_or_else(|| format!("{} is not transferable", target_id))?;
+        .ok_or_else(|| ErrorKind::WrongObjectType {
+            id: target_id.clone(),
+            expected: "transferable",
+        })?;
 
-    prepend_go_to_if_far(creep, target_pos, RANGE_TRANSFER)?;
-    creep.obj.transfer_amount(target, resource, amount);
+    if !prepend_go_to_if_far(creep, target_pos, RANGE_TRANSFER)? {
+        return Ok(());
+    }
+    let ret = creep.obj.transfer_amount(target, resource, amount);
 
     creep.memory_mut().actions.pop_front();
 
     // FIXME: Creeps aren't handled
-    // FIXME: Check if it worked
 
-    Ok(())
+    check(ret)
 }
 
 fn withdraw_all(creep: &mut Creep, target_id: &Id, resource: ResourceType) -> Res<()> {
@@ -304,16 +370,19 @@ fn withdraw_all(creep: &mut Creep, target_id: &Id, resource: ResourceType) -> Re
     let target_pos = target.pos();
     let target = target
         .as_withdrawable()
-        .ok_or_else(|| format!("{} is not withdrawable", target_id))?;
+        .ok_or_else(|| ErrorKind::WrongObjectType {
+            id: target_id.clone(),
+            expected: "withdrawable",
+        })?;
 
-    prepend_go_to_if_far(creep, target_pos, RANGE_WITHDRAW)?;
-    creep.obj.withdraw_all(target, resource);
+    if !prepend_go_to_if_far(creep, target_pos, RANGE_WITHDRAW)? {
+        return Ok(());
+    }
+    let ret = creep.obj.withdraw_all(target, resource);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn withdraw_amount(
@@ -326,274 +395,350 @@ fn withdraw_amount(
     let target_pos = target.pos();
     let target = target
         .as_withdrawable()
-        .ok_or_else(|| format!("{} is not withdrawable", target_id))?;
+        .ok_or_else(|| ErrorKind::WrongObjectType {
+            id: target_id.clone(),
+            expected: "withdrawable",
+        })?;
 
-    prepend_go_to_if_far(creep, target_pos, RANGE_WITHDRAW)?;
-    creep.obj.withdraw_amount(target, resource, amount);
+    if !prepend_go_to_if_far(creep, target_pos, RANGE_WITHDRAW)? {
+        return Ok(());
+    }
+    let ret = creep.obj.withdraw_amount(target, resource, amount);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn pickup(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target: Resource = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    prepend_go_to_if_far(creep, target.pos(), RANGE_TRANSFER)?;
-    creep.obj.pickup(&target);
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_TRANSFER)? {
+        return Ok(());
+    }
+    let ret = creep.obj.pickup(&target);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 pub fn harvest(creep: &mut Creep, target_id: &Id) -> Res<()> {
-    let target: Source = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+    // Dedicated harvesters keep going and let the rest drop
+    if creep.memory().role != Role::Harvester && is_full(creep) {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
 
-    prepend_go_to_if_far(creep, target.pos(), RANGE_HARVEST)?;
-    creep.obj.harvest(&target);
+    let target: Source = screeps::game::get_object_typed(&target_id)?
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
+
+    // Harvesters next to a link fill it instead of dropping what they carry
+    if is_full(creep) {
+        let link = creep
+            .obj
+            .pos()
+            .find_in_range(find::MY_STRUCTURES, RANGE_TRANSFER)
+            .into_iter()
+            .find_map(|s| match s {
+                Structure::Link(link) if link.energy() < link.energy_capacity() => Some(link),
+                _ => None,
+            });
+        if let Some(link) = link {
+            creep.obj.transfer_all(&link, ResourceType::Energy);
+        }
+    }
 
-    // FIXME: Check if it worked
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_HARVEST)? {
+        return Ok(());
+    }
+    let ret = creep.obj.harvest(&target);
 
-    Ok(())
+    // Sources run dry until they regenerate
+    match ret {
+        ReturnCode::NotEnough => Ok(()),
+        ret => check(ret),
+    }
 }
 
 fn build(creep: &mut Creep, site_id: &Id) -> Res<()> {
-    let site: ConstructionSite = screeps::game::get_object_typed(&site_id)?
-        .ok_or_else(|| format!("no object with id {}", site_id))?;
+    if is_empty(creep) {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
 
-    prepend_go_to_if_far(creep, site.pos(), RANGE_BUILD)?;
-    creep.obj.build(&site);
+    // The site is gone once it's built
+    let site: ConstructionSite = match screeps::game::get_object_typed(&site_id)? {
+        Some(site) => site,
+        None => {
+            creep.memory_mut().actions.pop_front();
+            return Ok(());
+        }
+    };
 
-    //    creep.memory().actions.pop_front();
+    if !prepend_go_to_if_far(creep, site.pos(), RANGE_BUILD)? {
+        return Ok(());
+    }
+    let ret = creep.obj.build(&site);
 
-    // FIXME: Check if it worked
     // FIXME: If not enough energy, get more energy
-    // FIXME: If done, remove job
 
-    Ok(())
+    check(ret)
 }
 
 fn dismantle(creep: &mut Creep, target_id: &Id) -> Res<()> {
+    // Done once there's nothing left of it
+    if get_object_erased(target_id).is_none() {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
     let target = get_structure_from_id(target_id)?;
 
-    prepend_go_to_if_far(creep, target.pos(), RANGE_DISMANTLE)?;
-    creep.obj.dismantle(&target);
-
-    //    creep.memory().actions.pop_front();
-
-    // FIXME: Check if it worked
-    // FIXME: If done, remove job
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_DISMANTLE)? {
+        return Ok(());
+    }
+    let ret = creep.obj.dismantle(&target);
 
-    Ok(())
+    check(ret)
 }
 
 fn repair(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target = get_structure_from_id(target_id)?;
 
-    prepend_go_to_if_far(creep, target.pos(), RANGE_REPAIR)?;
-    creep.obj.repair(&target);
-
-    //    creep.memory().actions.pop_front();
+    let repaired = target
+        .as_attackable()
+        .map_or(true, |a| a.hits() >= a.hits_max());
+    if is_empty(creep) || repaired {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
 
-    // FIXME: Check if it worked
-    // FIXME: If not enough energy, get more energy
-    // FIXME: If done, remove job
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_REPAIR)? {
+        return Ok(());
+    }
+    let ret = creep.obj.repair(&target);
 
-    Ok(())
+    check(ret)
 }
 
 fn fortify(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target = get_structure_from_id(target_id)?;
 
-    prepend_go_to_if_far(creep, target.pos(), RANGE_REPAIR)?;
-    creep.obj.repair(&target);
-
-    //    creep.memory().actions.pop_front();
+    // Walls and ramparts are only built up to the target for the room's level
+    let rcl = creep.obj.room().controller().map_or(0, |c| c.level());
+    let hits = target.as_attackable().map_or(0, |a| a.hits());
+    if is_empty(creep) || hits >= fortify_target(rcl) {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
 
-    // FIXME: Check if it worked
-    // FIXME: If not enough energy, get more energy
-    // FIXME: Do for number of ticks?
-    // FIXME: If done, remove job
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_REPAIR)? {
+        return Ok(());
+    }
+    let ret = creep.obj.repair(&target);
 
-    Ok(())
+    check(ret)
 }
 
 fn controller_attack(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let controller: StructureController = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_ATTACK)?;
-    creep.obj.attack_controller(&controller);
+    if !prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_ATTACK)? {
+        return Ok(());
+    }
+    let ret = creep.obj.attack_controller(&controller);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn controller_claim(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let controller: StructureController = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_CLAIM)?;
-    creep.obj.claim_controller(&controller);
+    if !prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_CLAIM)? {
+        return Ok(());
+    }
+    let ret = creep.obj.claim_controller(&controller);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn controller_upgrade(creep: &mut Creep, target_id: &Id) -> Res<()> {
+    if is_empty(creep) {
+        creep.memory_mut().actions.pop_front();
+        return Ok(());
+    }
+
     let controller: StructureController = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_UPGRADE)?;
-    creep.obj.upgrade_controller(&controller);
+    if !prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_UPGRADE)? {
+        return Ok(());
+    }
+    let ret = creep.obj.upgrade_controller(&controller);
 
     //    creep.memory().actions.pop_front();
 
-    // FIXME: Check if it worked
     // FIXME: If not enough energy, get more energy
     // FIXME: If done, remove job
 
-    Ok(())
+    check(ret)
 }
 
 fn controller_reserve(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let controller: StructureController = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_RESERVE)?;
-    creep.obj.reserve_controller(&controller);
+    if !prepend_go_to_if_far(creep, controller.pos(), RANGE_CONTROLLER_RESERVE)? {
+        return Ok(());
+    }
+    let ret = creep.obj.reserve_controller(&controller);
 
-    // FIXME: Check if it worked
     // TODO: stop at some point?
 
-    Ok(())
+    check(ret)
 }
 
 fn heal(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target: ScreepsCreep = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    // TODO: See if close?
-    creep.obj.heal(&target);
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_HEAL)? {
+        return Ok(());
+    }
+    let ret = creep.obj.heal(&target);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn heal_ranged(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target: ScreepsCreep = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    // TODO: See if close?
-    creep.obj.ranged_heal(&target);
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_HEAL_RANGED)? {
+        return Ok(());
+    }
+    let ret = creep.obj.ranged_heal(&target);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn attack_melee(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target: ScreepsCreep = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    // TODO: See if close?
-    creep.obj.attack(&target);
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_ATTACK_MELEE)? {
+        return Ok(());
+    }
+    let ret = creep.obj.attack(&target);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn attack_ranged(creep: &mut Creep, target_id: &Id) -> Res<()> {
     let target: ScreepsCreep = screeps::game::get_object_typed(&target_id)?
-        .ok_or_else(|| format!("no object with id {}", target_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(target_id.clone()))?;
 
-    // TODO: See if close?
-    creep.obj.ranged_attack(&target);
+    if !prepend_go_to_if_far(creep, target.pos(), RANGE_ATTACK_RANGED)? {
+        return Ok(());
+    }
+    let ret = creep.obj.ranged_attack(&target);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn attack_ranged_mass(creep: &mut Creep) -> Res<()> {
     // TODO: See if close?
-    creep.obj.ranged_mass_attack();
+    let ret = creep.obj.ranged_mass_attack();
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
-fn get_boosted(creep: &mut Creep, lab_id: &Id) -> Res<()> {
+fn get_boosted(creep: &mut Creep, lab_id: &Id, compound: ResourceType) -> Res<()> {
     let lab: StructureLab = screeps::game::get_object_typed(&lab_id)?
-        .ok_or_else(|| format!("no object with id {}", lab_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(lab_id.clone()))?;
 
-    prepend_go_to_if_far(creep, lab.pos(), RANGE_BOOST)?;
-    lab.boost_creep(&creep.obj, None);
+    if !prepend_go_to_if_far(creep, lab.pos(), RANGE_BOOST)? {
+        return Ok(());
+    }
+    // Wait while the lab is emptied of anything else and filled up
+    let parts = creep.obj.body().len() as u32;
+    if lab.mineral_type() != compound
+        || lab.mineral_amount() < BOOST_MINERAL * parts
+        || lab.energy() < BOOST_ENERGY * parts
+    {
+        return Ok(());
+    }
+    let ret = lab.boost_creep(&creep.obj, None);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn get_renewed(creep: &mut Creep, spawn_id: &Id) -> Res<()> {
     let spawn: StructureSpawn = screeps::game::get_object_typed(&spawn_id)?
-        .ok_or_else(|| format!("no object with id {}", spawn_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(spawn_id.clone()))?;
 
-    prepend_go_to_if_far(creep, spawn.pos(), RANGE_RENEW)?;
-    spawn.renew_creep(&creep.obj);
+    if !prepend_go_to_if_far(creep, spawn.pos(), RANGE_RENEW)? {
+        return Ok(());
+    }
+    let ret = spawn.renew_creep(&creep.obj);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
-
-    Ok(())
+    check(ret)
 }
 
 fn get_recycled(creep: &mut Creep, spawn_id: &Id) -> Res<()> {
     let spawn: StructureSpawn = screeps::game::get_object_typed(&spawn_id)?
-        .ok_or_else(|| format!("no object with id {}", spawn_id))?;
+        .ok_or_else(|| ErrorKind::ObjectNotFound(spawn_id.clone()))?;
 
-    prepend_go_to_if_far(creep, spawn.pos(), RANGE_RECYCLE)?;
-    spawn.recycle_creep(&creep.obj);
+    if !prepend_go_to_if_far(creep, spawn.pos(), RANGE_RECYCLE)? {
+        return Ok(());
+    }
+    let ret = spawn.recycle_creep(&creep.obj);
 
     creep.memory_mut().actions.pop_front();
 
-    // FIXME: Check if it worked
+    check(ret)
+}
 
-    Ok(())
+fn is_full(creep: &Creep) -> bool {
+    creep.obj.carry_capacity() > 0 && creep.obj.carry_total() >= creep.obj.carry_capacity()
+}
+
+fn is_empty(creep: &Creep) -> bool {
+    creep.obj.carry_of(ResourceType::Energy) == 0
+}
+
+/// An `IntentFailed` error for anything but `Ok`.
+fn check(ret: ReturnCode) -> Res<()> {
+    match ret {
+        ReturnCode::Ok => Ok(()),
+        ret => Err(ErrorKind::IntentFailed(ret).into()),
+    }
 }
 
 fn get_structure_from_id(id: &Id) -> Res<Structure> {
     let reference: Reference = get_object_erased(id)
-        .ok_or_else(|| format!("no object with id {}", id))?
+        .ok_or_else(|| ErrorKind::ObjectNotFound(id.clone()))?
         .into();
     Ok(reference.into_expected_type()?)
 }
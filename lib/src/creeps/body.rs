@@ -0,0 +1,230 @@
+use screeps::constants::Part;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+const MAX_CREEP_SIZE: usize = 50;
+
+fn default_max_size() -> usize {
+    MAX_CREEP_SIZE
+}
+
+/// How many MOVE parts a body needs to stay at full speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Movement {
+    /// Only the MOVE parts written in the template.
+    #[serde(rename = "fixed")]
+    Fixed,
+    /// One MOVE for every two other parts.
+    #[serde(rename = "road")]
+    Road,
+    /// One MOVE for every other part.
+    #[serde(rename = "plain")]
+    Plain,
+}
+
+impl Default for Movement {
+    fn default() -> Self {
+        Movement::Fixed
+    }
+}
+
+impl Movement {
+    fn moves_for(self, parts: usize) -> usize {
+        match self {
+            Movement::Fixed => 0,
+            Movement::Road => (parts + 1) / 2,
+            Movement::Plain => parts,
+        }
+    }
+}
+
+/// A body description that grows with the energy available.
+///
+/// The body is `prefix`, then as many copies of `segment` as fit, then
+/// `suffix`, plus enough MOVE parts for `movement`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BodyTemplate {
+    #[serde(default)]
+    pub prefix: Vec<Part>,
+    #[serde(default)]
+    pub segment: Vec<Part>,
+    #[serde(default)]
+    pub suffix: Vec<Part>,
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+    #[serde(default)]
+    pub max_segments: Option<usize>,
+    #[serde(default)]
+    pub movement: Movement,
+}
+
+js_serializable!(BodyTemplate);
+js_deserializable!(BodyTemplate);
+
+/// An `equip` entry as it's written in the config: a template, or the fixed
+/// list of parts older configs have.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EquipEntry {
+    Fixed(Vec<Part>),
+    Template(BodyTemplate),
+}
+
+impl From<EquipEntry> for BodyTemplate {
+    fn from(entry: EquipEntry) -> Self {
+        match entry {
+            EquipEntry::Fixed(parts) => BodyTemplate {
+                prefix: parts,
+                ..BodyTemplate::fixed()
+            },
+            EquipEntry::Template(template) => template,
+        }
+    }
+}
+
+impl BodyTemplate {
+    /// A template without any parts, that doesn't add MOVE parts.
+    fn fixed() -> Self {
+        BodyTemplate {
+            prefix: vec![],
+            segment: vec![],
+            suffix: vec![],
+            max_size: MAX_CREEP_SIZE,
+            max_segments: None,
+            movement: Movement::Fixed,
+        }
+    }
+
+    /// Builds the largest body this template allows for `energy`, or `None`
+    /// if not even the body without any segments is affordable.
+    pub fn scale(&self, energy: u32) -> Option<Vec<Part>> {
+        let max_size = self.max_size.min(MAX_CREEP_SIZE);
+        let mut best = None;
+        let mut segments = 0;
+
+        loop {
+            let body = self.with_segments(segments);
+            if body.len() > max_size || body_cost(&body) > energy {
+                break;
+            }
+            // Templates made only of segments have nothing without one
+            if !body.is_empty() {
+                best = Some(body);
+            }
+
+            segments += 1;
+            if self.segment.is_empty() || self.max_segments.map_or(false, |m| segments > m) {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// The cheapest body this template can produce.
+    pub fn minimum_cost(&self) -> u32 {
+        body_cost(&self.with_segments(0))
+    }
+
+    fn with_segments(&self, segments: usize) -> Vec<Part> {
+        let mut body = self.prefix.clone();
+        for _ in 0..segments {
+            body.extend_from_slice(&self.segment);
+        }
+        body.extend_from_slice(&self.suffix);
+
+        let others = body.iter().filter(|p| **p != Part::Move).count();
+        let moves = body.len() - others;
+        let needed = self.movement.moves_for(others);
+        if needed > moves {
+            body.extend((moves..needed).map(|_| Part::Move));
+        }
+
+        sort_body(&mut body);
+        body
+    }
+}
+
+pub fn body_cost(body: &[Part]) -> u32 {
+    body.iter().map(|p| p.cost()).sum()
+}
+
+/// Orders parts so damage is soaked by TOUGH first and HEAL survives longest.
+pub fn sort_body(body: &mut [Part]) {
+    body.sort_by_key(|p| match p {
+        Part::Tough => 0,
+        Part::Work => 1,
+        Part::Carry => 2,
+        Part::Claim => 3,
+        Part::Move => 4,
+        Part::Attack => 5,
+        Part::RangedAttack => 6,
+        Part::Heal => 7,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Part::*;
+
+    fn template(prefix: &[Part], segment: &[Part], movement: Movement) -> BodyTemplate {
+        BodyTemplate {
+            prefix: prefix.to_vec(),
+            segment: segment.to_vec(),
+            movement,
+            ..BodyTemplate::fixed()
+        }
+    }
+
+    #[test]
+    fn scale_fits_as_many_segments_as_energy_allows() {
+        let t = template(&[], &[Work, Carry, Move], Movement::Fixed);
+        assert_eq!(t.scale(199), None);
+        assert_eq!(t.scale(200), Some(vec![Work, Carry, Move]));
+        assert_eq!(
+            t.scale(650),
+            Some(vec![
+                Work, Work, Work, Carry, Carry, Carry, Move, Move, Move
+            ])
+        );
+    }
+
+    #[test]
+    fn scale_adds_moves_for_movement() {
+        let t = template(&[], &[Work, Work], Movement::Road);
+        assert_eq!(t.scale(300), Some(vec![Work, Work, Move]));
+        let t = template(&[Carry], &[], Movement::Plain);
+        assert_eq!(t.scale(1000), Some(vec![Carry, Move]));
+    }
+
+    #[test]
+    fn scale_respects_limits() {
+        let mut t = template(&[], &[Work, Move], Movement::Fixed);
+        t.max_segments = Some(2);
+        assert_eq!(t.scale(10_000).map(|b| b.len()), Some(4));
+
+        t.max_segments = None;
+        t.max_size = 5;
+        assert_eq!(t.scale(10_000).map(|b| b.len()), Some(4));
+
+        t.max_size = 100;
+        assert_eq!(t.scale(100_000).map(|b| b.len()), Some(MAX_CREEP_SIZE));
+    }
+
+    #[test]
+    fn scale_keeps_fixed_bodies() {
+        let t = BodyTemplate::from(EquipEntry::Fixed(vec![Move, Claim]));
+        assert_eq!(t.scale(650), Some(vec![Claim, Move]));
+        assert_eq!(t.scale(649), None);
+    }
+
+    #[test]
+    fn sort_body_puts_tough_first_and_heal_last() {
+        let mut body = vec![Heal, Move, RangedAttack, Attack, Tough, Carry, Claim, Work];
+        sort_body(&mut body);
+        assert_eq!(
+            body,
+            vec![Tough, Work, Carry, Claim, Move, Attack, RangedAttack, Heal]
+        );
+    }
+}
@@ -15,6 +15,8 @@ pub enum Role {
     Upgrader,
     #[serde(rename = "A")]
     Attacker,
+    #[serde(rename = "C")]
+    Claimer,
 }
 
 js_serializable!(Role);
@@ -35,6 +37,7 @@ impl fmt::Display for Role {
             Role::Builder => write!(f, "B"),
             Role::Upgrader => write!(f, "U"),
             Role::Attacker => write!(f, "A"),
+            Role::Claimer => write!(f, "C"),
         }
     }
 }
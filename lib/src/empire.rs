@@ -0,0 +1,207 @@
+use crate::{
+    config::Config,
+    creeps::{Action, Role},
+    error::Res,
+    registry::Registry,
+};
+use log::*;
+use screeps::{
+    find, game::map::get_room_linear_distance, HasId, HasPosition, Room as ScreepsRoom, RoomName,
+    RoomObjectProperties, StructureProperties, StructureType,
+};
+use std::collections::{HashMap, VecDeque};
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// Energy capacity needed to spawn a claimer.
+const CLAIMER_ENERGY: u32 = 650;
+const CLAIM_COOLDOWN: u32 = 600;
+const SUPPORT_COOLDOWN: u32 = 300;
+const DEFENSE_COOLDOWN: u32 = 300;
+
+/// Something the empire wants a room's Mayor to do this tick.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Directive {
+    /// Spawn a claimer for `target`.
+    SpawnClaimer { target: RoomName },
+    /// Spawn a builder to get `target` its first spawn.
+    SupportColony { target: RoomName },
+    /// Spawn a defender for `target`, which can't defend itself.
+    Defend { target: RoomName },
+}
+
+js_serializable!(Directive);
+js_deserializable!(Directive);
+
+impl Directive {
+    fn cooldown(&self) -> u32 {
+        match self {
+            Directive::SpawnClaimer { .. } => CLAIM_COOLDOWN,
+            Directive::SupportColony { .. } => SUPPORT_COOLDOWN,
+            Directive::Defend { .. } => DEFENSE_COOLDOWN,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct EmpireMemory {
+    /// Directives a helper room has acted on recently, and the tick it did.
+    #[serde(default)]
+    pub issued: Vec<(Directive, u32)>,
+}
+
+js_serializable!(EmpireMemory);
+js_deserializable!(EmpireMemory);
+
+/// Looks after everything that involves more than one room, and hands the
+/// results to each room's Mayor as directives.
+pub struct Empire<'a> {
+    config: &'a Config,
+    memory: Option<EmpireMemory>,
+    rooms: Vec<ScreepsRoom>,
+}
+
+impl<'a> Drop for Empire<'a> {
+    fn drop(&mut self) {
+        screeps::memory::root().set("empire", self.memory.take());
+    }
+}
+
+impl<'a> Empire<'a> {
+    pub fn new(config: &'a Config) -> Res<Self> {
+        let memory = Some(screeps::memory::root().get("empire")?.unwrap_or_default());
+        let rooms = screeps::game::rooms::values()
+            .into_iter()
+            .filter(|r| r.controller().map_or(false, |c| c.my()))
+            .collect();
+        Ok(Self {
+            config,
+            memory,
+            rooms,
+        })
+    }
+
+    pub fn memory(&self) -> &EmpireMemory {
+        self.memory
+            .as_ref()
+            .expect("empire.memory is not populated")
+    }
+
+    pub fn memory_mut(&mut self) -> &mut EmpireMemory {
+        self.memory
+            .as_mut()
+            .expect("empire.memory is not populated")
+    }
+
+    /// Works out this tick's directives, keyed by the room that should carry
+    /// them out.
+    pub fn run(&mut self, registry: &mut Registry) -> Res<HashMap<RoomName, Vec<Directive>>> {
+        let time = screeps::game::time();
+        self.memory_mut()
+            .issued
+            .retain(|(d, t)| time < t + d.cooldown());
+        self.direct_travellers(registry);
+
+        let mut wanted = Vec::new();
+        for target in &self.config.memory().expansion_targets {
+            let target = match RoomName::new(target) {
+                Ok(target) => target,
+                Err(_) => {
+                    warn!("invalid expansion target {}", target);
+                    continue;
+                }
+            };
+            match self.rooms.iter().find(|r| r.name() == target) {
+                Some(room) if room.find(find::MY_SPAWNS).is_empty() => {
+                    wanted.push(Directive::SupportColony { target });
+                }
+                Some(_) => {}
+                None => wanted.push(Directive::SpawnClaimer { target }),
+            }
+        }
+        for room in &self.rooms {
+            let hostiles = !room.find(find::HOSTILE_CREEPS).is_empty();
+            let towers = room
+                .find(find::MY_STRUCTURES)
+                .iter()
+                .any(|s| s.structure_type() == StructureType::Tower);
+            if hostiles && !towers {
+                wanted.push(Directive::Defend {
+                    target: room.name(),
+                });
+            }
+        }
+
+        let mut directives = HashMap::<RoomName, Vec<Directive>>::new();
+        for directive in wanted {
+            if self.memory().issued.iter().any(|(d, _)| *d == directive) {
+                continue;
+            }
+            let helper = match self.helper_for(&directive) {
+                Some(helper) => helper,
+                None => continue,
+            };
+            info!("{} is to {:?}", helper, directive);
+            directives.entry(helper).or_default().push(directive);
+        }
+
+        Ok(directives)
+    }
+
+    /// Records that a helper room queued the spawn for `directive`, so it
+    /// isn't handed out again until its cooldown is over.
+    pub fn confirm(&mut self, directive: Directive) {
+        let time = screeps::game::time();
+        self.memory_mut().issued.push((directive, time));
+    }
+
+    /// The closest owned room able to carry out `directive`.
+    fn helper_for(&self, directive: &Directive) -> Option<RoomName> {
+        let (target, energy) = match directive {
+            Directive::SpawnClaimer { target } => (*target, CLAIMER_ENERGY),
+            Directive::SupportColony { target } | Directive::Defend { target } => (*target, 0),
+        };
+        self.rooms
+            .iter()
+            .filter(|r| r.name() != target)
+            .filter(|r| !r.find(find::MY_SPAWNS).is_empty())
+            .filter(|r| r.energy_capacity_available() >= energy)
+            .min_by_key(|r| get_room_linear_distance(r.name(), target, false))
+            .map(|r| r.name())
+    }
+
+    /// Gives claimers and defenders that have reached their room something
+    /// to do there.
+    fn direct_travellers(&self, registry: &mut Registry) {
+        for creep in registry.creeps_mut() {
+            if !creep.memory().actions.is_empty() {
+                continue;
+            }
+            let room = creep.obj().room();
+            let pos = creep.obj().pos();
+
+            let action = match creep.memory().role {
+                Role::Claimer => room
+                    .controller()
+                    .filter(|c| !c.my())
+                    .map(|c| Action::ControllerClaim { target_id: c.id() }),
+                Role::Attacker => room
+                    .find(find::HOSTILE_CREEPS)
+                    .into_iter()
+                    .min_by_key(|h| pos.get_range_to(&h.pos()))
+                    .map(|h| Action::AttackMelee { target_id: h.id() }),
+                _ => None,
+            };
+            if let Some(action) = action {
+                let mut actions = VecDeque::new();
+                if let Some(target) = action.pos() {
+                    actions.push_back(Action::GoToRanged {
+                        pos: target,
+                        range: 1,
+                    });
+                }
+                actions.push_back(action);
+                creep.memory_mut().actions = actions;
+            }
+        }
+    }
+}
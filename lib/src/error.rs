@@ -1,2 +1,140 @@
-pub type Error = Box<dyn std::error::Error>;
+use crate::Id;
+use screeps::ReturnCode;
+use std::fmt;
+use stdweb::private::ConversionError;
+
 pub type Res<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// Nothing, or nothing usable, in memory at the given path.
+    MissingMemory(String),
+    ObjectNotFound(Id),
+    WrongObjectType {
+        id: Id,
+        expected: &'static str,
+    },
+    IntentFailed(ReturnCode),
+    Config(String),
+    Conversion(ConversionError),
+    Other(String),
+}
+
+impl ErrorKind {
+    /// A short name for the kind, for filtering and counting.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::MissingMemory(_) => "MissingMemory",
+            ErrorKind::ObjectNotFound(_) => "ObjectNotFound",
+            ErrorKind::WrongObjectType { .. } => "WrongObjectType",
+            ErrorKind::IntentFailed(_) => "IntentFailed",
+            ErrorKind::Config(_) => "Config",
+            ErrorKind::Conversion(_) => "Conversion",
+            ErrorKind::Other(_) => "Other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::MissingMemory(path) => write!(f, "undefined or null memory at {}", path),
+            ErrorKind::ObjectNotFound(id) => write!(f, "no object with id {}", id),
+            ErrorKind::WrongObjectType { id, expected } => write!(f, "{} is not {}", id, expected),
+            ErrorKind::IntentFailed(code) => write!(f, "intent failed with {:?}", code),
+            ErrorKind::Config(msg) => write!(f, "bad config: {}", msg),
+            ErrorKind::Conversion(e) => write!(f, "conversion failed: {}", e),
+            ErrorKind::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Which entity an error happened to, and when.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub creep: Option<String>,
+    pub room: Option<String>,
+    pub tick: u32,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    context: Context,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            context: Context {
+                tick: screeps::game::time(),
+                ..Context::default()
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn with_creep(mut self, name: &str) -> Self {
+        self.context.creep = Some(name.to_string());
+        self
+    }
+
+    pub fn with_room(mut self, name: &str) -> Self {
+        self.context.room = Some(name.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.kind.name(), self.kind)?;
+        if let Some(creep) = &self.context.creep {
+            write!(f, " (creep {})", creep)?;
+        }
+        if let Some(room) = &self.context.room {
+            write!(f, " (room {})", room)?;
+        }
+        write!(f, " at tick {}", self.context.tick)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Conversion(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind)
+    }
+}
+
+impl From<ConversionError> for Error {
+    fn from(e: ConversionError) -> Self {
+        Error::new(ErrorKind::Conversion(e))
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::new(ErrorKind::Other(msg))
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(msg: &'a str) -> Self {
+        Error::new(ErrorKind::Other(msg.to_string()))
+    }
+}
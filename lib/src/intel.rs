@@ -0,0 +1,72 @@
+use crate::{error::Res, rooms::RoomKind};
+use log::*;
+use screeps::{find, HasPosition, Position, ResourceType, Room as ScreepsRoom};
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// What we last saw of a room we don't own.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoomIntel {
+    pub kind: RoomKind,
+    pub last_seen: u32,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub controller: Option<Position>,
+    #[serde(default)]
+    pub sources: Vec<Position>,
+    #[serde(default)]
+    pub mineral: Option<(ResourceType, Position)>,
+    #[serde(default)]
+    pub hostiles: u32,
+}
+
+js_serializable!(RoomIntel);
+js_deserializable!(RoomIntel);
+
+/// The lightweight manager for visible rooms without a Mayor: it records
+/// what's there and keeps an eye on the rooms we mine.
+pub struct Intel {
+    obj: ScreepsRoom,
+    kind: RoomKind,
+}
+
+impl Intel {
+    pub fn new(obj: ScreepsRoom, kind: RoomKind) -> Self {
+        Self { obj, kind }
+    }
+
+    pub fn run(&self) -> Res<()> {
+        let controller = self.obj.controller();
+        let owner = controller.as_ref().and_then(|c| {
+            c.owner_name()
+                .or_else(|| c.reservation().map(|r| r.username))
+        });
+        let hostiles = self.obj.find(find::HOSTILE_CREEPS).len() as u32;
+
+        if hostiles > 0 && (self.kind == RoomKind::Remote || self.kind == RoomKind::Reserved) {
+            warn!("{} hostiles in {}", hostiles, self.obj.name());
+        }
+
+        let intel = RoomIntel {
+            kind: self.kind,
+            last_seen: screeps::game::time(),
+            owner,
+            controller: controller.map(|c| c.pos()),
+            sources: self
+                .obj
+                .find(find::SOURCES)
+                .iter()
+                .map(|s| s.pos())
+                .collect(),
+            mineral: self
+                .obj
+                .find(find::MINERALS)
+                .first()
+                .map(|m| (m.mineral_type(), m.pos())),
+            hostiles,
+        };
+        screeps::memory::root().path_set(&format!("intel.{}", self.obj.name()), intel);
+
+        Ok(())
+    }
+}
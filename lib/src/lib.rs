@@ -4,10 +4,16 @@ extern crate serde_derive;
 pub mod config;
 pub mod controllers;
 pub mod creeps;
+pub mod empire;
 pub mod error;
+pub mod intel;
 pub mod logging;
 pub mod mayor;
 pub mod names;
+pub mod planner;
+pub mod reactions;
+pub mod registry;
 pub mod rooms;
+pub mod terrain;
 
 pub type Id = String;
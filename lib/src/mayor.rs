@@ -1,114 +1,646 @@
 use crate::{
     config::Config,
-    creeps::{Creep, Role},
-    error::Res,
+    creeps::{body_cost, Action, BodyTemplate, Creep, CreepMemory, Role},
+    empire::Directive,
+    error::{Error, ErrorKind, Res},
     names::get_random_name,
     rooms::Room,
 };
 use log::*;
 use screeps::{
-    find, memory::MemoryReference, ReturnCode, RoomObjectProperties, SpawnOptions, StructureSpawn,
+    constants::Part, find, game::map::get_room_linear_distance, look, Direction, HasId,
+    HasPosition, Position, ReturnCode, RoomObjectProperties, SpawnOptions, StructureProperties,
+    StructureSpawn, StructureType, Terrain,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+pub use jobs::{Capacity, Job};
+pub use labs::Reaction;
+pub use mines::Mine;
+pub use repairs::RepairTask;
+pub use reservations::{Reservation, Reservations};
+pub use roads::Traffic;
+pub use spawn_queue::{Priority, Requester, SpawnQueue, SpawnRequest};
+
+mod construction;
+mod jobs;
+mod labs;
+mod links;
+mod mines;
+mod planning;
+mod population;
+mod recovery;
+mod repairs;
+mod reservations;
+mod roads;
+mod spawn_queue;
+mod towers;
+
+/// How long a request from `roles_to_spawn` waits before it's dropped.
+const SPAWN_REQUEST_TTL: u32 = 1500;
+const CREEP_SPAWN_TIME: u32 = 3;
+/// How close a replacement gets to the work site before it takes over.
+const HANDOVER_RANGE: u32 = 1;
+const ROOM_SIZE: u32 = 50;
 
 pub struct Mayor<'a> {
-    pub room: Room,
+    pub room: &'a mut Room,
     pub config: &'a Config,
+    pub directives: Vec<Directive>,
 }
 
 impl<'a> Mayor<'a> {
-    pub fn new(room: Room, config: &'a Config) -> Self {
-        Self { room, config }
+    pub fn new(room: &'a mut Room, config: &'a Config, directives: Vec<Directive>) -> Self {
+        Self {
+            room,
+            config,
+            directives,
+        }
     }
 
-    pub fn run(self) -> Res<()> {
-        let my_creeps = screeps::game::creeps::values()
-            .into_iter()
-            .map(|c| Creep::new(c))
-            .collect::<Res<Vec<_>>>()?
-            .into_iter()
-            .filter(|c| c.memory().home == self.room.obj().name())
-            .collect::<Vec<_>>();
-        self.determine_spawns()?;
+    /// Runs the room for this tick; `my_creeps` are the creeps whose home it
+    /// is. Returns the directives it followed.
+    pub fn run(mut self, my_creeps: &mut [&mut Creep]) -> Res<Vec<Directive>> {
+        self.plan_layout()?;
+        self.update_mines()?;
+        self.record_traffic(my_creeps);
+        self.plan_roads()?;
+        self.place_construction_sites()?;
+        self.update_repairs(my_creeps)?;
+        self.run_towers()?;
+        self.run_links()?;
+        self.check_recovery(my_creeps)?;
+        self.direct_recovery_creeps(my_creeps)?;
+        self.hand_over(my_creeps)?;
+        self.assign_jobs(my_creeps)?;
+        self.schedule_replacements(my_creeps)?;
+        let followed = self.follow_directives()?;
+        self.determine_spawns(my_creeps)?;
+        // Before spawning, so boost labs aren't freed while their creep is
+        // between the queue and the room's creeps
+        self.run_labs(my_creeps)?;
+        self.process_spawn_queue(my_creeps)?;
 
-        Ok(())
+        Ok(followed)
     }
 
-    pub fn determine_spawns(&self) -> Res<()> {
-        // If there's nothing to spawn, just return
-        let room_name = self.room.obj().name().to_array_string();
-        if !self
-            .config
-            .memory()
-            .roles_to_spawn
-            .contains_key(room_name.as_str())
-        {
-            return Ok(());
+    /// Queues the creeps the empire asked this room for, returning the
+    /// directives that were queued.
+    pub fn follow_directives(&mut self) -> Res<Vec<Directive>> {
+        let mut followed = Vec::new();
+        for directive in std::mem::replace(&mut self.directives, Vec::new()) {
+            let (role, priority, requester, target) = match directive {
+                Directive::SpawnClaimer { target } => {
+                    (Role::Claimer, Priority::High, Requester::Expansion, target)
+                }
+                Directive::SupportColony { target } => (
+                    Role::Builder,
+                    Priority::Normal,
+                    Requester::Expansion,
+                    target,
+                ),
+                Directive::Defend { target } => {
+                    (Role::Attacker, Priority::High, Requester::Defense, target)
+                }
+            };
+            let equip = match self.config.memory().role_equip.get(&role) {
+                Some(equip) => equip.clone(),
+                None => {
+                    warn!("no equip for {} to follow {:?}", role, directive);
+                    continue;
+                }
+            };
+
+            let mut actions = VecDeque::new();
+            actions.push_back(Action::GoToRoom { room_id: target });
+            let mut request = SpawnRequest::new(role, &equip, priority, requester)
+                .actions(actions)
+                .expires_in(SPAWN_REQUEST_TTL);
+            // Helpers belong to the room they help, so its Mayor puts them
+            // to work once they arrive
+            if role != Role::Claimer {
+                request = request.home(&target.to_string());
+            }
+            if let Err(e) = self.request_spawn(request) {
+                warn!("can't follow {:?}: {}", directive, e);
+                continue;
+            }
+            followed.push(directive);
         }
 
-        // If there are no spawns, just return
-        let mut spawns = self.room.obj().find(find::MY_SPAWNS);
-        if spawns.len() == 0 {
-            return Ok(());
+        Ok(followed)
+    }
+
+    /// Adds a request to this room's spawn queue. Creeps staying in the room
+    /// get boosted first with whatever `role_boosts` it has a lab for.
+    /// Requests whose smallest body the room can't hold aren't queued.
+    pub fn request_spawn(&mut self, mut request: SpawnRequest) -> Res<()> {
+        let template = self
+            .template(&request.equip)
+            .ok_or_else(|| ErrorKind::Config(format!("{} not found in equip", request.equip)))?;
+        let capacity = self.room.obj().energy_capacity_available();
+        if template.minimum_cost() > capacity {
+            self.room
+                .memory_mut()
+                .unspawnable
+                .insert(request.equip.clone(), capacity);
+            return Err(ErrorKind::Config(format!(
+                "{} can't be spawned with {} energy capacity",
+                request.equip, capacity
+            ))
+            .into());
         }
 
-        // If the spawn is currently spawning, just return
-        if spawns[0].is_spawning() {
+        if request.home.is_none() {
+            let config = self.config;
+            let boosts = config.memory().role_boosts.get(&request.role);
+            let parts = template.scale(capacity).map_or(0, |body| body.len() as u32);
+            for compound in boosts.into_iter().flatten().rev() {
+                if let Some(lab_id) = self.boost_lab(*compound, parts) {
+                    let compound = *compound;
+                    request
+                        .actions
+                        .push_front(Action::GetBoosted { lab_id, compound });
+                }
+            }
+        }
+        self.room.memory_mut().spawn_queue.push(request);
+        Ok(())
+    }
+
+    /// Queues a successor for every creep that would die before a new creep
+    /// could be spawned and walk to its work site.
+    pub fn schedule_replacements(&mut self, creeps: &mut [&mut Creep]) -> Res<()> {
+        let spawns = self.room.obj().find(find::MY_SPAWNS);
+        if spawns.is_empty() {
             return Ok(());
         }
 
+        for creep in creeps.iter_mut() {
+            if creep.obj().spawning()
+                || creep.memory().replacement_requested
+                || creep.memory().equip.is_empty()
+                || creep.memory().equip == recovery::RECOVERY_EQUIP
+            {
+                continue;
+            }
+
+            let site = creep.work_site();
+            let spawn_time = creep.obj().body().len() as u32 * CREEP_SPAWN_TIME;
+            let travel = spawns
+                .iter()
+                .map(|s| travel_distance(&s.pos(), &site))
+                .min()
+                .unwrap_or(0);
+            let ttl = creep.obj().ticks_to_live();
+            if ttl > spawn_time + travel {
+                continue;
+            }
+
+            let name = creep.obj().name();
+            let role = creep.memory().role;
+            info!("Replacing {} ({}), {} ticks to live", name, role, ttl);
+            let request = SpawnRequest::new(
+                role,
+                &creep.memory().equip,
+                Priority::High,
+                Requester::Mayor,
+            )
+            .destination(site)
+            .replaces(&name)
+            .expires_in(ttl);
+            // Marked either way, so a replacement that can't be spawned is
+            // only reported once
+            if let Err(e) = self.request_spawn(request) {
+                warn!("can't replace {}: {}", name, e);
+            }
+            creep.memory_mut().replacement_requested = true;
+        }
+
+        Ok(())
+    }
+
+    /// Queues a spawn for every entry of the room's population targets that
+    /// has neither a living creep nor a pending request.
+    pub fn determine_spawns(&mut self, creeps: &[&mut Creep]) -> Res<()> {
+        // If there's nothing to spawn, just return
+        let roles_to_spawn = match self.population_targets() {
+            Some(roles_to_spawn) => roles_to_spawn,
+            None => return Ok(()),
+        };
+
         // Get the number of creeps in each role by iterating
         // through the creeps in the room and incrementing the
         // counter for that role
-        let mut current_roles = HashMap::<Role, u8>::new();
-        for creep in self.room.obj().find(find::MY_CREEPS) {
-            if let Ok(Some(role)) = creep.memory().get("role") {
-                let counter = current_roles.entry(role).or_insert(0);
+        let mut current_roles = HashMap::<Role, usize>::new();
+        for creep in creeps.iter() {
+            let counter = current_roles.entry(creep.memory().role).or_insert(0);
+            *counter += 1;
+        }
+
+        // Requests already in the queue count as creeps on their way
+        for request in self.room.memory().spawn_queue.iter() {
+            if request.requester == Requester::Mayor {
+                let counter = current_roles.entry(request.role).or_insert(0);
                 *counter += 1;
             }
         }
 
-        let roles_to_spawn = &self.config.memory().roles_to_spawn[room_name.as_str()];
+        let capacity = self.room.obj().energy_capacity_available();
         for (role, equip_name) in roles_to_spawn {
-            let counter = current_roles.entry(*role).or_insert(0);
+            let counter = current_roles.entry(role).or_insert(0);
 
             if *counter == 0 {
-                match self.spawn(spawns.remove(0), *role, equip_name) {
-                    Ok(_) => {
-                        break;
-                    }
-                    Err(e) => {
-                        warn!("{}", e);
-                        continue;
-                    }
+                // Already reported, and the room hasn't grown since
+                if self.room.memory().unspawnable.get(&equip_name) == Some(&capacity) {
+                    continue;
+                }
+                let request =
+                    SpawnRequest::new(role, &equip_name, Priority::Normal, Requester::Mayor)
+                        .expires_in(SPAWN_REQUEST_TTL);
+                if let Err(e) = self.request_spawn(request) {
+                    warn!("{}", e.with_room(&self.room.obj().name().to_string()));
+                }
+            } else {
+                *counter -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hands queued requests to the room's idle spawns, highest priority
+    /// first, each to the idle spawn closest to where the creep is headed.
+    pub fn process_spawn_queue(&mut self, creeps: &[&mut Creep]) -> Res<()> {
+        let time = screeps::game::time();
+        self.room.memory_mut().spawn_queue.prune(time);
+
+        let spawns = self.room.obj().find(find::MY_SPAWNS);
+        let mut idle = spawns
+            .iter()
+            .filter(|s| !s.is_spawning())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // `energyAvailable` isn't updated until next tick, so keep track of
+        // what this tick's spawns have already spent
+        let mut energy = self.room.obj().energy_available();
+        let emergency =
+            self.room.memory().recovering || self.room.obj().find(find::MY_CREEPS).is_empty();
+
+        let mut names = Vec::new();
+        while !idle.is_empty() {
+            let request = match self.room.memory().spawn_queue.front() {
+                Some(request) => request.clone(),
+                None => break,
+            };
+
+            // Lower priority requests wait too, so they can't starve this one
+            let body = match self.plan_body(&request, energy, emergency) {
+                Ok(BodyPlan::Spawn(body)) => body,
+                Ok(BodyPlan::Wait(reason)) => {
+                    debug!(
+                        "{}: {} ({}) {}",
+                        self.room.obj().name(),
+                        request.equip,
+                        request.role,
+                        reason
+                    );
+                    break;
+                }
+                Err(e) => {
+                    warn!("{}", e);
+                    self.room.memory_mut().spawn_queue.pop_front();
+                    continue;
+                }
+            };
+
+            let spawn_index = match request.destination {
+                Some(dest) => idle
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, s)| travel_distance(&s.pos(), &dest))
+                    .map(|(i, _)| i)
+                    .unwrap_or(0),
+                None => 0,
+            };
+            let spawn = idle[spawn_index].clone();
+            let name = get_random_name(&spawn.name(), &names);
+            let directions = spawn_directions(&spawn, &spawns);
+
+            let predecessor = request.replaces.as_ref().and_then(|replaces| {
+                creeps
+                    .iter()
+                    .find(|c| c.obj().name() == *replaces)
+                    .map(|c| c.memory())
+            });
+
+            match self.spawn(&spawn, &request, &body, &name, &directions, predecessor)? {
+                ReturnCode::Ok => {
+                    self.room.memory_mut().spawn_queue.pop_front();
+                    idle.remove(spawn_index);
+                    names.push(name);
+                    energy = energy.saturating_sub(body_cost(&body));
+                }
+                ReturnCode::NotEnough | ReturnCode::Busy => break,
+                // Taken by another room this tick, so try another
+                ReturnCode::NameExists => names.push(name),
+                ret => {
+                    let e = Error::from(ErrorKind::IntentFailed(ret))
+                        .with_room(&self.room.obj().name().to_string());
+                    warn!(
+                        "{} can't spawn {} ({}): {}",
+                        spawn.name(),
+                        request.equip,
+                        request.role,
+                        e
+                    );
+                    self.room.memory_mut().spawn_queue.pop_front();
                 }
             }
+        }
+
+        Ok(())
+    }
 
-            *counter -= 1;
+    fn template(&self, equip: &str) -> Option<BodyTemplate> {
+        match self.config.memory().equip.get(equip) {
+            Some(template) => Some(template.clone()),
+            None if equip == recovery::RECOVERY_EQUIP => Some(recovery::recovery_template()),
+            None => None,
+        }
+    }
+
+    /// Decides between waiting for the room to fill up and spawning a
+    /// smaller body with the `energy` there is now.
+    fn plan_body(&self, request: &SpawnRequest, energy: u32, emergency: bool) -> Res<BodyPlan> {
+        let template = self
+            .template(&request.equip)
+            .ok_or_else(|| ErrorKind::Config(format!("{} not found in equip", request.equip)))?;
+
+        let capacity = self.room.obj().energy_capacity_available();
+        let full = template.scale(capacity).ok_or_else(|| {
+            ErrorKind::Config(format!(
+                "{} can't be spawned with {} energy capacity",
+                request.equip, capacity
+            ))
+        })?;
+        let full_cost = body_cost(&full);
+        if full_cost <= energy {
+            return Ok(BodyPlan::Spawn(full));
+        }
+
+        // With no creeps left nobody will refill the extensions, so don't
+        // wait for them
+        let waited = screeps::game::time().saturating_sub(request.created);
+        if !emergency && waited < max_energy_wait(request.priority) {
+            return Ok(BodyPlan::Wait(format!(
+                "waiting for energy ({}/{}), {} ticks so far",
+                energy, full_cost, waited
+            )));
+        }
+
+        match template.scale(energy) {
+            Some(body) => {
+                info!(
+                    "Downgrading {} ({}) from {} to {} energy",
+                    request.equip,
+                    request.role,
+                    full_cost,
+                    body_cost(&body)
+                );
+                Ok(BodyPlan::Spawn(body))
+            }
+            None => Ok(BodyPlan::Wait(format!(
+                "can't afford the smallest body ({}/{})",
+                energy,
+                template.minimum_cost()
+            ))),
+        }
+    }
+
+    /// Once a replacement reaches its predecessor's work site, gives it the
+    /// predecessor's actions and reservations and sends the predecessor off
+    /// to be recycled, so the two don't work the same target side by side.
+    pub fn hand_over(&mut self, creeps: &mut [&mut Creep]) -> Res<()> {
+        let spawns = self.room.obj().find(find::MY_SPAWNS);
+        for i in 0..creeps.len() {
+            let old_name = match &creeps[i].memory().replaces {
+                Some(name) => name.clone(),
+                None => continue,
+            };
+            let successor = &creeps[i];
+            if successor.obj().spawning() {
+                continue;
+            }
+            let arrived = successor.memory().work_pos.map_or(true, |p| {
+                successor.obj().pos().get_range_to(&p) <= HANDOVER_RANGE
+            });
+            let old = creeps.iter().position(|c| c.obj().name() == old_name);
+            if old.is_some() && !arrived {
+                continue;
+            }
+
+            let new_name = creeps[i].obj().name();
+            if let Some(old) = old {
+                let predecessor = &mut creeps[old];
+                let pos = predecessor.obj().pos();
+                let recycle = spawns
+                    .iter()
+                    .min_by_key(|s| pos.get_range_to(&s.pos()))
+                    .map(|s| Action::GetRecycled { spawn_id: s.id() });
+                let memory = predecessor.memory_mut();
+                let actions = std::mem::replace(&mut memory.actions, recycle.into_iter().collect());
+                memory.work_pos = None;
+
+                debug!("{} takes over from {}", new_name, old_name);
+                creeps[i].memory_mut().actions = actions;
+                self.room
+                    .memory_mut()
+                    .reservations
+                    .hand_over(&old_name, &new_name);
+            }
+            creeps[i].memory_mut().replaces = None;
         }
 
         Ok(())
     }
 
-    pub fn spawn(&self, spawn: StructureSpawn, role: Role, equip_name: &str) -> Res<ReturnCode> {
-        let body = self
-            .config
-            .memory()
-            .equip
-            .get(equip_name)
-            .ok_or_else(|| format!("{} not found in equip", role))?;
-        let name = get_random_name();
-        // FIXME: create a CreepMemory
-        let memory = MemoryReference::new();
-        memory.set("role", format!("{}", role));
-        memory.set("home", format!("{}", spawn.room().name()));
-        let options = SpawnOptions::new().memory(memory);
-
-        let ret = spawn.spawn_creep_with_options(body, &name, &options);
+    /// Spawns `body` for `request`, checking with a dry run first so a
+    /// failure doesn't leave anything half done. A spawn with no free
+    /// `directions` lets the game pick. A replacement takes over the work
+    /// of its `predecessor`.
+    pub fn spawn(
+        &self,
+        spawn: &StructureSpawn,
+        request: &SpawnRequest,
+        body: &[Part],
+        name: &str,
+        directions: &[Direction],
+        predecessor: Option<&CreepMemory>,
+    ) -> Res<ReturnCode> {
+        let role = request.role;
+        // The game rejects an empty list of directions
+        let with_directions = |options: SpawnOptions| {
+            if directions.is_empty() {
+                options
+            } else {
+                options.directions(directions)
+            }
+        };
+        let dry_run = with_directions(SpawnOptions::new().dry_run(true));
+        let ret = spawn.spawn_creep_with_options(body, name, &dry_run);
+        if ret != ReturnCode::Ok {
+            return Ok(ret);
+        }
+
+        // A replacement heads to where its predecessor works, and takes over
+        // its actions once it's there
+        let (actions, work_pos) = match predecessor {
+            Some(p) => {
+                let work_pos = p.work_pos.or(request.destination);
+                let go_to = work_pos.map(|pos| Action::GoToRanged {
+                    pos,
+                    range: HANDOVER_RANGE,
+                });
+                let boosts = request
+                    .actions
+                    .iter()
+                    .filter(|a| match a {
+                        Action::GetBoosted { .. } => true,
+                        _ => false,
+                    })
+                    .cloned();
+                (boosts.chain(go_to).collect(), work_pos)
+            }
+            None => (request.actions.clone(), request.destination),
+        };
+
+        let memory = CreepMemory {
+            home: request
+                .home
+                .clone()
+                .unwrap_or_else(|| spawn.room().name().to_string()),
+            role,
+            actions,
+            spawned: screeps::game::time(),
+            cost: body_cost(body),
+            equip: request.equip.clone(),
+            work_pos,
+            replacement_requested: false,
+            replaces: request.replaces.clone(),
+        }
+        .to_memory_reference()?;
+        let options = with_directions(SpawnOptions::new().memory(memory));
+
+        let ret = spawn.spawn_creep_with_options(body, name, &options);
         if ret == ReturnCode::Ok {
-            info!("Spawning {} ({})", name, role);
+            info!(
+                "Spawning {} ({}) for {} energy",
+                name,
+                role,
+                body_cost(body)
+            );
         }
 
         Ok(ret)
     }
 }
+
+/// What to do about the request at the front of the spawn queue.
+enum BodyPlan {
+    Spawn(Vec<Part>),
+    Wait(String),
+}
+
+/// How long a request waits for a full body before settling for a smaller
+/// one.
+fn max_energy_wait(priority: Priority) -> u32 {
+    match priority {
+        Priority::Critical => 0,
+        Priority::High => 50,
+        Priority::Normal => 300,
+        Priority::Low => u32::max_value(),
+    }
+}
+
+/// Rough number of ticks needed to walk from `a` to `b`.
+fn travel_distance(a: &Position, b: &Position) -> u32 {
+    if a.room_name() == b.room_name() {
+        a.get_range_to(b)
+    } else {
+        ROOM_SIZE * get_room_linear_distance(a.room_name(), b.room_name(), false)
+    }
+}
+
+/// Directions a new creep can leave `spawn` in, pointing away from the
+/// room's other spawns first so their creeps don't block each other.
+fn spawn_directions(spawn: &StructureSpawn, spawns: &[StructureSpawn]) -> Vec<Direction> {
+    let pos = spawn.pos();
+    let terrain = spawn.room().get_terrain();
+    let others = spawns
+        .iter()
+        .filter(|s| s.id() != spawn.id())
+        .map(|s| s.pos())
+        .collect::<Vec<_>>();
+
+    let mut directions = ALL_DIRECTIONS
+        .iter()
+        .filter_map(|&dir| {
+            let (dx, dy) = direction_offset(dir);
+            let x = pos.x() as i32 + dx;
+            let y = pos.y() as i32 + dy;
+            if x < 1 || x > 48 || y < 1 || y > 48 {
+                return None;
+            }
+            let target = Position::new(x as u32, y as u32, pos.room_name());
+            if terrain.get(target.x(), target.y()) == Terrain::Wall {
+                return None;
+            }
+            let blocked = spawn
+                .room()
+                .look_for_at(look::STRUCTURES, &target)
+                .iter()
+                .any(|s| is_obstacle(s.structure_type()));
+            if blocked {
+                return None;
+            }
+            let clearance = others.iter().map(|o| o.get_range_to(&target)).min();
+            Some((dir, clearance.unwrap_or(0)))
+        })
+        .collect::<Vec<_>>();
+    directions.sort_by_key(|(_, clearance)| std::cmp::Reverse(*clearance));
+
+    directions.into_iter().map(|(dir, _)| dir).collect()
+}
+
+const ALL_DIRECTIONS: [Direction; 8] = [
+    Direction::Top,
+    Direction::TopRight,
+    Direction::Right,
+    Direction::BottomRight,
+    Direction::Bottom,
+    Direction::BottomLeft,
+    Direction::Left,
+    Direction::TopLeft,
+];
+
+fn direction_offset(dir: Direction) -> (i32, i32) {
+    match dir {
+        Direction::Top => (0, -1),
+        Direction::TopRight => (1, -1),
+        Direction::Right => (1, 0),
+        Direction::BottomRight => (1, 1),
+        Direction::Bottom => (0, 1),
+        Direction::BottomLeft => (-1, 1),
+        Direction::Left => (-1, 0),
+        Direction::TopLeft => (-1, -1),
+    }
+}
+
+fn is_obstacle(structure_type: StructureType) -> bool {
+    match structure_type {
+        StructureType::Road | StructureType::Container | StructureType::Rampart => false,
+        _ => true,
+    }
+}
@@ -0,0 +1,209 @@
+use super::{planning::tile, Mayor};
+use crate::{
+    error::Res,
+    planner::{Layout, ROAD_RCL},
+    terrain::Tile,
+};
+use log::*;
+use screeps::{
+    find, HasPosition, Position, ResourceType, ReturnCode, Structure, StructureProperties,
+    StructureType,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The game's limit on construction sites, across all rooms.
+const MAX_CONSTRUCTION_SITES: usize = 100;
+/// Sites placed by one room in one tick.
+const SITES_PER_TICK: usize = 10;
+/// How often the layout is checked for missing structures, besides right
+/// after the controller levels up.
+const CONSTRUCTION_INTERVAL: u32 = 50;
+
+/// Controller levels mine containers are built at.
+const SOURCE_CONTAINER_RCL: u8 = 2;
+const MINERAL_CONTAINER_RCL: u8 = 6;
+
+/// What gets built first when there's more to build than sites to place.
+const BUILD_ORDER: &[StructureType] = &[
+    StructureType::Spawn,
+    StructureType::Extension,
+    StructureType::Tower,
+    StructureType::Storage,
+    StructureType::Link,
+    StructureType::Container,
+    StructureType::Terminal,
+    StructureType::Extractor,
+    StructureType::Lab,
+    StructureType::Road,
+    StructureType::Rampart,
+    StructureType::Wall,
+];
+
+impl<'a> Mayor<'a> {
+    /// Places construction sites for whatever the layout has at the current
+    /// controller level that isn't built yet, clearing away structures left
+    /// behind by previous owners that are in the way.
+    pub fn place_construction_sites(&mut self) -> Res<()> {
+        let room = self.room.obj();
+        let rcl = room.controller().map_or(0, |c| c.level() as u8);
+        let levelled_up = rcl > self.room.memory().built_rcl;
+        if !levelled_up && screeps::game::time() % CONSTRUCTION_INTERVAL != 0 {
+            return Ok(());
+        }
+        let planned = match &self.room.memory().layout {
+            Some(layout) => self.planned_sites(layout, rcl),
+            None => return Ok(()),
+        };
+        let keep = self.planned_structures().unwrap_or_default();
+
+        let placed = screeps::game::construction_sites::values().len();
+        let mut budget = MAX_CONSTRUCTION_SITES
+            .saturating_sub(placed)
+            .min(SITES_PER_TICK);
+
+        let mut built: HashMap<Tile, Vec<Structure>> = HashMap::new();
+        for structure in room.find(find::STRUCTURES) {
+            built
+                .entry(tile(&structure.pos()))
+                .or_insert_with(Vec::new)
+                .push(structure);
+        }
+        let sites = room
+            .find(find::CONSTRUCTION_SITES)
+            .iter()
+            .map(|s| tile(&s.pos()))
+            .collect::<HashSet<_>>();
+
+        for (structure_type, pos) in planned {
+            if budget == 0 {
+                break;
+            }
+            let at = tile(&pos);
+            if sites.contains(&at) {
+                continue;
+            }
+            let existing = built.get(&at).map_or(&[][..], |s| &s[..]);
+            if existing
+                .iter()
+                .any(|s| s.structure_type() == structure_type && is_ours(s))
+            {
+                continue;
+            }
+
+            let blockers = existing
+                .iter()
+                .filter(|s| conflicts(s, structure_type))
+                .collect::<Vec<_>>();
+            if !blockers.is_empty() {
+                for blocker in blockers.iter().filter(|s| is_obsolete(s, &keep)) {
+                    info!(
+                        "{}: removing {:?} at {} for a {:?}",
+                        room.name(),
+                        blocker.structure_type(),
+                        pos,
+                        structure_type
+                    );
+                    blocker.destroy();
+                }
+                // Build once the tile's clear; our own structures stay put
+                continue;
+            }
+
+            match room.create_construction_site(&pos, structure_type) {
+                ReturnCode::Ok => budget -= 1,
+                ReturnCode::Full => break,
+                ReturnCode::RclNotEnough => {}
+                ret => debug!(
+                    "{}: can't place a {:?} at {}: {:?}",
+                    room.name(),
+                    structure_type,
+                    pos,
+                    ret
+                ),
+            }
+        }
+
+        self.room.memory_mut().built_rcl = rcl;
+
+        Ok(())
+    }
+
+    /// Everything that should exist at `rcl`, most important first.
+    fn planned_sites(&self, layout: &Layout, rcl: u8) -> Vec<(StructureType, Position)> {
+        let name = self.room.obj().name();
+        let mut planned = layout
+            .up_to(rcl)
+            .map(|s| {
+                let pos = Position::new(s.x.into(), s.y.into(), name);
+                (s.structure, s.rcl, pos)
+            })
+            .collect::<Vec<_>>();
+
+        for (resource, mines) in &self.room.memory().mines {
+            let container_rcl = if *resource == ResourceType::Energy {
+                SOURCE_CONTAINER_RCL
+            } else {
+                MINERAL_CONTAINER_RCL
+            };
+            if container_rcl <= rcl {
+                planned.extend(
+                    mines
+                        .iter()
+                        .map(|m| (StructureType::Container, container_rcl, m.standing)),
+                );
+            }
+        }
+
+        if ROAD_RCL <= rcl {
+            planned.extend(
+                self.room
+                    .memory()
+                    .roads
+                    .iter()
+                    .map(|pos| (StructureType::Road, ROAD_RCL, *pos)),
+            );
+        }
+
+        let order = |structure_type| {
+            BUILD_ORDER
+                .iter()
+                .position(|s| *s == structure_type)
+                .unwrap_or(BUILD_ORDER.len())
+        };
+        planned.sort_by_key(|(structure_type, rcl, _)| (order(*structure_type), *rcl));
+
+        planned
+            .into_iter()
+            .map(|(structure_type, _, pos)| (structure_type, pos))
+            .collect()
+    }
+}
+
+/// Ours, or not owned by anyone like roads and containers.
+pub(super) fn is_ours(structure: &Structure) -> bool {
+    structure.as_owned().map_or(true, |s| s.my())
+}
+
+/// Safe to destroy: owned by another player, or a road, wall or container
+/// the plan doesn't have there.
+fn is_obsolete(structure: &Structure, planned: &HashSet<(StructureType, Tile)>) -> bool {
+    let structure_type = structure.structure_type();
+    match structure_type {
+        StructureType::Road | StructureType::Wall | StructureType::Container => {
+            !is_ours(structure) || !planned.contains(&(structure_type, tile(&structure.pos())))
+        }
+        _ => !is_ours(structure),
+    }
+}
+
+/// Whether `existing` keeps `planned` from being built on the same tile.
+/// Only other players' ramparts are in the way.
+fn conflicts(existing: &Structure, planned: StructureType) -> bool {
+    use StructureType::*;
+    match (existing.structure_type(), planned) {
+        (Rampart, _) => !is_ours(existing),
+        (_, Rampart) => false,
+        (Road, Container) | (Container, Road) => false,
+        (a, b) => a != b,
+    }
+}
@@ -0,0 +1,429 @@
+use super::{links::LinkRole, travel_distance, Mayor, Reservations};
+use crate::{
+    creeps::{Action, Creep, Role},
+    error::Res,
+    Id,
+};
+use log::*;
+use screeps::{
+    constants::Part, find, HasId, HasPosition, Position, ResourceType, StructureProperties,
+    StructureType,
+};
+use std::collections::VecDeque;
+
+/// Energy worth fetching from a container or picking up off the ground.
+const MIN_ENERGY: u32 = 50;
+
+/// A piece of work in the room that a creep can be assigned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Job {
+    Harvest {
+        source_id: Id,
+        pos: Position,
+    },
+    Refill {
+        target_id: Id,
+        pos: Position,
+        needed: u32,
+    },
+    Pickup {
+        resource_id: Id,
+        pos: Position,
+        amount: u32,
+    },
+    Build {
+        site_id: Id,
+        pos: Position,
+    },
+    Repair {
+        target_id: Id,
+        pos: Position,
+    },
+    Fortify {
+        target_id: Id,
+        pos: Position,
+    },
+    /// Carry `amount` of a mineral from the storage or terminal to a lab.
+    FillLab {
+        lab_id: Id,
+        pos: Position,
+        resource: ResourceType,
+        amount: u32,
+    },
+    /// Carry what's in a lab to the storage.
+    EmptyLab {
+        lab_id: Id,
+        pos: Position,
+        resource: ResourceType,
+        amount: u32,
+    },
+    Upgrade {
+        controller_id: Id,
+        pos: Position,
+    },
+}
+
+/// How much of a job there is to hand out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capacity {
+    /// A number of creeps.
+    Creeps(usize),
+    /// An amount of resource, shared between however many creeps it takes.
+    Amount(u32),
+}
+
+impl Job {
+    pub fn target_id(&self) -> &Id {
+        match self {
+            Job::Harvest { source_id, .. } => source_id,
+            Job::Refill { target_id, .. } => target_id,
+            Job::Pickup { resource_id, .. } => resource_id,
+            Job::Build { site_id, .. } => site_id,
+            Job::Repair { target_id, .. } | Job::Fortify { target_id, .. } => target_id,
+            Job::FillLab { lab_id, .. } | Job::EmptyLab { lab_id, .. } => lab_id,
+            Job::Upgrade { controller_id, .. } => controller_id,
+        }
+    }
+
+    pub fn pos(&self) -> Position {
+        match self {
+            Job::Harvest { pos, .. }
+            | Job::Refill { pos, .. }
+            | Job::Pickup { pos, .. }
+            | Job::Build { pos, .. }
+            | Job::Repair { pos, .. }
+            | Job::Fortify { pos, .. }
+            | Job::FillLab { pos, .. }
+            | Job::EmptyLab { pos, .. }
+            | Job::Upgrade { pos, .. } => *pos,
+        }
+    }
+
+    /// Higher is more urgent.
+    fn priority(&self) -> u32 {
+        match self {
+            Job::Harvest { .. } => 8,
+            Job::Refill { .. } => 7,
+            Job::Pickup { .. } => 6,
+            Job::Repair { .. } => 5,
+            Job::Build { .. } => 4,
+            Job::FillLab { .. } | Job::EmptyLab { .. } => 3,
+            Job::Fortify { .. } => 2,
+            Job::Upgrade { .. } => 1,
+        }
+    }
+
+    pub fn capacity(&self) -> Capacity {
+        match self {
+            Job::Harvest { .. } => Capacity::Creeps(1),
+            Job::Refill { needed, .. } => Capacity::Amount(*needed),
+            Job::Pickup { amount, .. }
+            | Job::FillLab { amount, .. }
+            | Job::EmptyLab { amount, .. } => Capacity::Amount(*amount),
+            Job::Build { .. } => Capacity::Creeps(3),
+            Job::Repair { .. } => Capacity::Creeps(1),
+            Job::Fortify { .. } => Capacity::Creeps(2),
+            Job::Upgrade { .. } => Capacity::Creeps(usize::max_value()),
+        }
+    }
+
+    fn is_available(&self, reservations: &Reservations) -> bool {
+        match self.capacity() {
+            Capacity::Creeps(n) => reservations.count(self.target_id()) < n,
+            Capacity::Amount(a) => reservations.amount(self.target_id()) < a,
+        }
+    }
+
+    fn needs_energy(&self) -> bool {
+        match self {
+            Job::Harvest { .. }
+            | Job::Pickup { .. }
+            | Job::FillLab { .. }
+            | Job::EmptyLab { .. } => false,
+            Job::Refill { .. }
+            | Job::Build { .. }
+            | Job::Repair { .. }
+            | Job::Fortify { .. }
+            | Job::Upgrade { .. } => true,
+        }
+    }
+
+    fn suits(&self, role: Role) -> bool {
+        match (role, self) {
+            (Role::Generic, _) => true,
+            (Role::Harvester, Job::Harvest { .. }) => true,
+            (Role::Transporter, Job::Refill { .. })
+            | (Role::Transporter, Job::Pickup { .. })
+            | (Role::Transporter, Job::FillLab { .. })
+            | (Role::Transporter, Job::EmptyLab { .. }) => true,
+            (Role::Builder, Job::Build { .. })
+            | (Role::Builder, Job::Repair { .. })
+            | (Role::Builder, Job::Fortify { .. })
+            | (Role::Builder, Job::Upgrade { .. }) => true,
+            (Role::Upgrader, Job::Upgrade { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Mayor<'a> {
+    /// Everything in the room that currently needs doing.
+    pub fn enumerate_jobs(&self) -> Vec<Job> {
+        let room = self.room.obj();
+        let mut jobs = Vec::new();
+
+        for source in room.find(find::SOURCES) {
+            // Harvesters work from the mine's standing spot where it's known
+            let pos = self.mine(&source.id()).map_or(source.pos(), |m| m.standing);
+            jobs.push(Job::Harvest {
+                source_id: source.id(),
+                pos,
+            });
+        }
+
+        for structure in room.find(find::MY_STRUCTURES) {
+            match structure.structure_type() {
+                StructureType::Spawn | StructureType::Extension | StructureType::Tower => {}
+                _ => continue,
+            }
+            let needed = structure
+                .as_can_store_energy()
+                .map_or(0, |s| s.energy_capacity() - s.energy());
+            if needed > 0 {
+                jobs.push(Job::Refill {
+                    target_id: structure.id(),
+                    pos: structure.pos(),
+                    needed,
+                });
+            }
+        }
+
+        for resource in room.find(find::DROPPED_RESOURCES) {
+            if resource.resource_type() == ResourceType::Energy && resource.amount() >= MIN_ENERGY {
+                jobs.push(Job::Pickup {
+                    resource_id: resource.id(),
+                    pos: resource.pos(),
+                    amount: resource.amount(),
+                });
+            }
+        }
+
+        for site in room.find(find::MY_CONSTRUCTION_SITES) {
+            jobs.push(Job::Build {
+                site_id: site.id(),
+                pos: site.pos(),
+            });
+        }
+
+        jobs.extend(self.lab_jobs());
+
+        for repair in self.creep_repairs() {
+            let (target_id, pos) = (repair.target_id.clone(), repair.pos);
+            jobs.push(if repair.fortify {
+                Job::Fortify { target_id, pos }
+            } else {
+                Job::Repair { target_id, pos }
+            });
+        }
+
+        if let Some(controller) = room.controller() {
+            if controller.my() {
+                jobs.push(Job::Upgrade {
+                    controller_id: controller.id(),
+                    pos: controller.pos(),
+                });
+            }
+        }
+
+        jobs
+    }
+
+    /// Gives every idle creep the most urgent nearby job its role can do,
+    /// reserves its targets and writes the actions for it into the creep's
+    /// queue.
+    pub fn assign_jobs(&mut self, creeps: &mut [&mut Creep]) -> Res<()> {
+        self.room.memory_mut().reservations.refresh(creeps);
+        let jobs = self.enumerate_jobs();
+
+        for creep in creeps.iter_mut() {
+            if !creep.memory().actions.is_empty() || creep.obj().spawning() {
+                continue;
+            }
+
+            let role = creep.memory().role;
+            let pos = creep.obj().pos();
+            let reservations = &self.room.memory().reservations;
+            let job = jobs
+                .iter()
+                .filter(|j| j.suits(role) && j.is_available(reservations))
+                .max_by_key(|j| {
+                    (
+                        j.priority(),
+                        u32::max_value() - travel_distance(&pos, &j.pos()),
+                    )
+                });
+            let job = match job {
+                Some(job) => job,
+                None => continue,
+            };
+
+            let (actions, claims) = match self.job_actions(creep, job) {
+                Some(planned) => planned,
+                None => continue,
+            };
+            debug!("{} takes {:?}", creep.obj().name(), job);
+
+            let name = creep.obj().name();
+            let reservations = &mut self.room.memory_mut().reservations;
+            for (target, amount) in claims {
+                reservations.reserve(&target, &name, amount);
+            }
+            if let Job::Harvest { pos, .. } = job {
+                creep.memory_mut().work_pos = Some(*pos);
+            }
+            creep.memory_mut().actions = actions;
+        }
+
+        Ok(())
+    }
+
+    /// The actions for `creep` to do `job`, and the reservations they need.
+    fn job_actions(&self, creep: &Creep, job: &Job) -> Option<(VecDeque<Action>, Vec<(Id, u32)>)> {
+        let mut actions = VecDeque::new();
+        let mut claims = Vec::new();
+
+        let capacity = creep.obj().carry_capacity();
+        let mut energy = creep.obj().carry_of(ResourceType::Energy);
+        let lab_load = match job {
+            // Minerals need a creep that isn't carrying anything else
+            Job::FillLab { amount, .. } | Job::EmptyLab { amount, .. } => {
+                if creep.obj().carry_total() > 0 {
+                    return None;
+                }
+                (*amount).min(capacity)
+            }
+            _ => 0,
+        };
+        if job.needs_energy() && energy == 0 {
+            let (action, claim) = self.fetch_energy(creep, job)?;
+            actions.push_back(action);
+            claims.extend(claim);
+            energy = capacity;
+        }
+
+        let amount = match job {
+            Job::Refill { .. } => energy,
+            Job::Pickup { .. } => capacity - creep.obj().carry_total(),
+            Job::FillLab { .. } | Job::EmptyLab { .. } => lab_load,
+            _ => 0,
+        };
+        claims.push((job.target_id().clone(), amount));
+
+        if let Job::FillLab { resource, .. } = job {
+            actions.push_back(Action::WithdrawAmount {
+                target_id: self.mineral_store(*resource)?,
+                resource: *resource,
+                amount: lab_load,
+            });
+        }
+
+        // Dedicated harvesters stand on the mine's container spot
+        if let Job::Harvest { pos, .. } = job {
+            if creep.memory().role == Role::Harvester && creep.obj().pos() != *pos {
+                actions.push_back(Action::GoTo { pos: *pos });
+            }
+        }
+        actions.push_back(match job {
+            Job::Harvest { source_id, .. } => Action::Harvest {
+                target_id: source_id.clone(),
+            },
+            Job::Refill { target_id, .. } => Action::TransferAll {
+                target_id: target_id.clone(),
+                resource: ResourceType::Energy,
+            },
+            Job::Pickup { resource_id, .. } => Action::Pickup {
+                target_id: resource_id.clone(),
+            },
+            Job::Build { site_id, .. } => Action::Build {
+                site_id: site_id.clone(),
+            },
+            Job::Repair { target_id, .. } => Action::Repair {
+                target_id: target_id.clone(),
+            },
+            Job::Fortify { target_id, .. } => Action::Fortify {
+                target_id: target_id.clone(),
+            },
+            Job::FillLab {
+                lab_id, resource, ..
+            } => Action::TransferAll {
+                target_id: lab_id.clone(),
+                resource: *resource,
+            },
+            Job::EmptyLab {
+                lab_id, resource, ..
+            } => Action::WithdrawAmount {
+                target_id: lab_id.clone(),
+                resource: *resource,
+                amount: lab_load,
+            },
+            Job::Upgrade { controller_id, .. } => Action::ControllerUpgrade {
+                target_id: controller_id.clone(),
+            },
+        });
+        if let Job::EmptyLab { resource, .. } = job {
+            actions.push_back(Action::TransferAll {
+                target_id: self.room.obj().storage()?.id(),
+                resource: *resource,
+            });
+        }
+
+        Some((actions, claims))
+    }
+
+    /// Where an empty creep should get energy for `job` from: the closest
+    /// storage, container or receiving link with enough unreserved energy,
+    /// else a source if it can harvest. The controller link is only for
+    /// upgrading.
+    fn fetch_energy(&self, creep: &Creep, job: &Job) -> Option<(Action, Option<(Id, u32)>)> {
+        let room = self.room.obj();
+        let pos = creep.obj().pos();
+        let reservations = &self.room.memory().reservations;
+        let link = match job {
+            Job::Upgrade { .. } => LinkRole::Controller,
+            _ => LinkRole::Hub,
+        };
+
+        let stored = room
+            .find(find::STRUCTURES)
+            .into_iter()
+            .filter(|s| match s.structure_type() {
+                StructureType::Storage | StructureType::Container => true,
+                // Source links are left for the harvesters to fill
+                StructureType::Link => self.link_role(&s.pos()) == Some(link),
+                _ => false,
+            })
+            .filter(|s| {
+                let energy = match s.as_has_store() {
+                    Some(store) => store.store_of(ResourceType::Energy),
+                    None => s.as_can_store_energy().map_or(0, |e| e.energy()),
+                };
+                energy.saturating_sub(reservations.amount(&s.id())) >= MIN_ENERGY
+            })
+            .min_by_key(|s| pos.get_range_to(&s.pos()));
+        if let Some(stored) = stored {
+            let action = Action::WithdrawAll {
+                target_id: stored.id(),
+                resource: ResourceType::Energy,
+            };
+            return Some((action, Some((stored.id(), creep.obj().carry_capacity()))));
+        }
+
+        if creep.obj().get_active_bodyparts(Part::Work) == 0 {
+            return None;
+        }
+        room.find(find::SOURCES_ACTIVE)
+            .into_iter()
+            .min_by_key(|s| pos.get_range_to(&s.pos()))
+            .map(|s| (Action::Harvest { target_id: s.id() }, None))
+    }
+}
@@ -0,0 +1,343 @@
+use super::{planning::tile, Job, Mayor};
+use crate::{
+    creeps::{Action, Creep, BOOST_MINERAL},
+    error::Res,
+    reactions::{components, next_reaction, LAB_REACTION_AMOUNT},
+    terrain::Tile,
+    Id,
+};
+use log::*;
+use screeps::{
+    find, CanStoreEnergy, HasCooldown, HasId, HasPosition, HasStore, ResourceType, ReturnCode,
+    Structure, StructureLab, StructureType,
+};
+use std::collections::{HashMap, HashSet};
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// How often a room with idle labs looks for something to make.
+const REACTION_INTERVAL: u32 = 100;
+/// Minerals aren't carried to a lab in smaller loads than this, unless
+/// that's all it still needs.
+const MIN_LAB_LOAD: u32 = 100;
+/// Output labs are emptied once they hold this much.
+const LAB_EMPTY_AT: u32 = 1000;
+/// What boost labs are filled up to.
+const BOOST_AMOUNT: u32 = 1500;
+
+/// A compound the room's labs are making.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reaction {
+    pub product: ResourceType,
+    pub inputs: [ResourceType; 2],
+    /// How much more of `product` to make.
+    pub remaining: u32,
+}
+
+js_serializable!(Reaction);
+js_deserializable!(Reaction);
+
+/// The room's labs by what they're used for.
+struct Labs {
+    /// The first two labs in the layout, in range of all the others.
+    inputs: Vec<StructureLab>,
+    outputs: Vec<StructureLab>,
+    /// Labs in `lab_spots`, and the compound they boost with.
+    boosting: Vec<(StructureLab, ResourceType)>,
+}
+
+impl<'a> Mayor<'a> {
+    /// Picks a compound to make when the labs are idle, following
+    /// `reaction_targets` down to whatever's missing, and runs the reaction
+    /// in every output lab that has room. Boost labs nobody is waiting for
+    /// any more go back to making compounds.
+    pub fn run_labs(&mut self, creeps: &[&mut Creep]) -> Res<()> {
+        self.release_boost_labs(creeps);
+        let labs = match self.labs() {
+            Some(labs) => labs,
+            None => return Ok(()),
+        };
+        if labs.inputs.len() < 2 || labs.outputs.is_empty() {
+            return Ok(());
+        }
+
+        let name = self.room.obj().name();
+        let reaction = match &self.room.memory().reaction {
+            Some(reaction) => reaction.clone(),
+            None => {
+                if screeps::game::time() % REACTION_INTERVAL == 0 {
+                    let reaction = self.choose_reaction();
+                    if let Some(reaction) = &reaction {
+                        info!(
+                            "{}: making {} {:?}",
+                            name, reaction.remaining, reaction.product
+                        );
+                    }
+                    self.room.memory_mut().reaction = reaction;
+                }
+                return Ok(());
+            }
+        };
+
+        let inputs = &labs.inputs;
+        let loaded = inputs.iter().zip(&reaction.inputs).all(|(lab, input)| {
+            lab.mineral_amount() >= LAB_REACTION_AMOUNT && lab.mineral_type() == *input
+        });
+        if !loaded {
+            if self.is_starved(inputs, &reaction) {
+                warn!("{}: out of inputs for {:?}", name, reaction.product);
+                self.room.memory_mut().reaction = None;
+            }
+            return Ok(());
+        }
+
+        let mut remaining = reaction.remaining;
+        for lab in &labs.outputs {
+            if remaining == 0 {
+                break;
+            }
+            let amount = lab.mineral_amount();
+            let holds_other = amount > 0 && lab.mineral_type() != reaction.product;
+            if lab.cooldown() > 0
+                || holds_other
+                || amount + LAB_REACTION_AMOUNT > lab.mineral_capacity()
+            {
+                continue;
+            }
+            match lab.run_reaction(&inputs[0], &inputs[1]) {
+                ReturnCode::Ok => remaining = remaining.saturating_sub(LAB_REACTION_AMOUNT),
+                ret => debug!("{}: lab at {} can't react: {:?}", name, lab.pos(), ret),
+            }
+        }
+
+        self.room.memory_mut().reaction = if remaining == 0 {
+            info!("{}: done making {:?}", name, reaction.product);
+            None
+        } else {
+            Some(Reaction {
+                remaining,
+                ..reaction
+            })
+        };
+
+        Ok(())
+    }
+
+    /// A lab loaded with `compound` for creeps to get boosted at, if the
+    /// room has any and enough of it to boost `parts` body parts. Labs it
+    /// sets aside in `lab_spots` are freed once no creep is waiting for them
+    /// any more, so whoever asks should queue the `GetBoosted` in the same
+    /// tick.
+    pub fn boost_lab(&mut self, compound: ResourceType, parts: u32) -> Option<Id> {
+        if let Some(id) = self.room.memory().lab_spots.get(&compound) {
+            return Some(id.clone());
+        }
+        if self.stock(compound) < BOOST_MINERAL * parts {
+            return None;
+        }
+        // The last output lab is the one reactions miss least
+        let id = self.labs()?.outputs.last()?.id();
+        let memory = self.room.memory_mut();
+        memory.lab_spots.insert(compound, id.clone());
+        memory.boost_labs.push(id.clone());
+        Some(id)
+    }
+
+    /// Carrying minerals in and out of labs, and energy into boost labs.
+    pub fn lab_jobs(&self) -> Vec<Job> {
+        let labs = match self.labs() {
+            Some(labs) => labs,
+            None => return vec![],
+        };
+        let reaction = self.room.memory().reaction.as_ref();
+        let mut jobs = vec![];
+
+        for (i, lab) in labs.inputs.iter().enumerate() {
+            let wanted = reaction.map(|r| (r.inputs[i], r.remaining));
+            jobs.extend(self.lab_job(lab, wanted));
+        }
+
+        for lab in &labs.outputs {
+            let amount = lab.mineral_amount();
+            let product = reaction.map(|r| r.product);
+            if amount >= LAB_EMPTY_AT || (amount > 0 && Some(lab.mineral_type()) != product) {
+                jobs.push(empty_lab(lab));
+            }
+        }
+
+        for (lab, compound) in &labs.boosting {
+            jobs.extend(self.lab_job(lab, Some((*compound, BOOST_AMOUNT))));
+            let needed = lab.energy_capacity() - lab.energy();
+            if needed > 0 {
+                jobs.push(Job::Refill {
+                    target_id: lab.id(),
+                    pos: lab.pos(),
+                    needed,
+                });
+            }
+        }
+
+        jobs
+    }
+
+    /// Where to take `resource` from to fill a lab: the storage or terminal,
+    /// whichever has more.
+    pub fn mineral_store(&self, resource: ResourceType) -> Option<Id> {
+        let room = self.room.obj();
+        let storage = room.storage().map(|s| (s.id(), s.store_of(resource)));
+        let terminal = room.terminal().map(|t| (t.id(), t.store_of(resource)));
+        storage
+            .into_iter()
+            .chain(terminal)
+            .filter(|(_, amount)| *amount > 0)
+            .max_by_key(|(_, amount)| *amount)
+            .map(|(id, _)| id)
+    }
+
+    /// Empties a lab of anything but `wanted`, and fills it with up to the
+    /// given amount of it.
+    fn lab_job(&self, lab: &StructureLab, wanted: Option<(ResourceType, u32)>) -> Option<Job> {
+        let amount = lab.mineral_amount();
+        let (resource, target) = match wanted {
+            Some((resource, _)) if amount > 0 && lab.mineral_type() != resource => {
+                return Some(empty_lab(lab))
+            }
+            Some(wanted) => wanted,
+            None if amount > 0 => return Some(empty_lab(lab)),
+            None => return None,
+        };
+
+        let missing = target.min(lab.mineral_capacity()).saturating_sub(amount);
+        let load = missing.min(self.stock(resource));
+        if load == 0 || load < MIN_LAB_LOAD.min(missing) {
+            return None;
+        }
+        Some(Job::FillLab {
+            lab_id: lab.id(),
+            pos: lab.pos(),
+            resource,
+            amount: load,
+        })
+    }
+
+    fn labs(&self) -> Option<Labs> {
+        let memory = self.room.memory();
+        let layout = memory.layout.as_ref()?;
+        let mut built = self
+            .room
+            .obj()
+            .find(find::MY_STRUCTURES)
+            .into_iter()
+            .filter_map(|s| match s {
+                Structure::Lab(lab) => Some((tile(&lab.pos()), lab)),
+                _ => None,
+            })
+            .collect::<HashMap<Tile, _>>();
+        if built.is_empty() {
+            return None;
+        }
+        let boosts = memory
+            .lab_spots
+            .iter()
+            .map(|(compound, id)| (id.clone(), *compound))
+            .collect::<HashMap<_, _>>();
+
+        let mut labs = Labs {
+            inputs: vec![],
+            outputs: vec![],
+            boosting: vec![],
+        };
+        for (i, planned) in layout.of_type(StructureType::Lab).enumerate() {
+            let lab = match built.remove(&planned.tile()) {
+                Some(lab) => lab,
+                None => continue,
+            };
+            if let Some(compound) = boosts.get(&lab.id()) {
+                labs.boosting.push((lab, *compound));
+            } else if i < 2 {
+                labs.inputs.push(lab);
+            } else {
+                labs.outputs.push(lab);
+            }
+        }
+
+        Some(labs)
+    }
+
+    fn choose_reaction(&self) -> Option<Reaction> {
+        let stock = |resource| self.stock(resource);
+        self.config
+            .memory()
+            .reaction_targets
+            .iter()
+            .find_map(|(target, amount)| {
+                let (product, remaining) = next_reaction(*target, *amount, &stock)?;
+                Some(Reaction {
+                    product,
+                    inputs: components(product)?,
+                    remaining,
+                })
+            })
+    }
+
+    /// Whether an input lab has run dry with none of its input left to
+    /// bring.
+    fn is_starved(&self, inputs: &[StructureLab], reaction: &Reaction) -> bool {
+        let reservations = &self.room.memory().reservations;
+        inputs.iter().zip(&reaction.inputs).any(|(lab, input)| {
+            let amount = lab.mineral_amount();
+            (amount == 0 || lab.mineral_type() == *input)
+                && amount < LAB_REACTION_AMOUNT
+                && self.stock(*input) < LAB_REACTION_AMOUNT
+                && reservations.amount(&lab.id()) == 0
+        })
+    }
+
+    /// How much of `resource` the storage and terminal hold.
+    fn stock(&self, resource: ResourceType) -> u32 {
+        let room = self.room.obj();
+        room.storage().map_or(0, |s| s.store_of(resource))
+            + room.terminal().map_or(0, |t| t.store_of(resource))
+    }
+
+    /// Frees the boost labs set aside by `boost_lab` that no creep, spawned
+    /// or queued, is on its way to.
+    fn release_boost_labs(&mut self, creeps: &[&mut Creep]) {
+        if self.room.memory().boost_labs.is_empty() {
+            return;
+        }
+        let queued = self
+            .room
+            .memory()
+            .spawn_queue
+            .iter()
+            .flat_map(|r| r.actions.iter());
+        let waiting = creeps
+            .iter()
+            .flat_map(|c| c.memory().actions.iter())
+            .chain(queued)
+            .filter_map(|a| match a {
+                Action::GetBoosted { lab_id, .. } => Some(lab_id.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let memory = self.room.memory_mut();
+        let released = memory
+            .boost_labs
+            .iter()
+            .filter(|id| !waiting.contains(*id))
+            .cloned()
+            .collect::<HashSet<_>>();
+        memory.lab_spots.retain(|_, id| !released.contains(id));
+        memory.boost_labs.retain(|id| !released.contains(id));
+    }
+}
+
+fn empty_lab(lab: &StructureLab) -> Job {
+    Job::EmptyLab {
+        lab_id: lab.id(),
+        pos: lab.pos(),
+        resource: lab.mineral_type(),
+        amount: lab.mineral_amount(),
+    }
+}
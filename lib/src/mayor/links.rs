@@ -0,0 +1,108 @@
+use super::{planning::tile, Mayor};
+use crate::{
+    error::Res,
+    terrain::{offset, range},
+    Id,
+};
+use log::*;
+use screeps::{
+    find, CanStoreEnergy, HasCooldown, HasId, HasPosition, Position, ReturnCode, Structure,
+    StructureLink,
+};
+use std::collections::HashMap;
+
+/// Links don't send less than this, to make the most of their cooldown.
+const LINK_SEND_MIN: u32 = 100;
+
+/// What a link is for, going by where the layout put it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkRole {
+    /// Next to a harvester, which fills it.
+    Source,
+    /// Next to the storage, where creeps take the energy from.
+    Hub,
+    /// Next to the upgraders.
+    Controller,
+}
+
+impl<'a> Mayor<'a> {
+    /// What the link at `pos` is for, if the layout planned one there.
+    pub fn link_role(&self, pos: &Position) -> Option<LinkRole> {
+        let layout = self.room.memory().layout.as_ref()?;
+        let at = tile(pos);
+        if offset(layout.anchor, 1, 0) == Some(at) {
+            Some(LinkRole::Hub)
+        } else if layout.upgrade_spot.map_or(false, |s| range(s, at) <= 1) {
+            Some(LinkRole::Controller)
+        } else if layout.source_spots.iter().any(|s| range(*s, at) <= 1) {
+            Some(LinkRole::Source)
+        } else {
+            None
+        }
+    }
+
+    /// Sends energy from source links to the controller link while it has
+    /// room, and to the hub link otherwise, and tops up the controller link
+    /// from the hub.
+    pub fn run_links(&mut self) -> Res<()> {
+        let mut by_role: HashMap<LinkRole, Vec<StructureLink>> = HashMap::new();
+        for structure in self.room.obj().find(find::MY_STRUCTURES) {
+            if let Structure::Link(link) = structure {
+                if let Some(role) = self.link_role(&link.pos()) {
+                    by_role.entry(role).or_insert_with(Vec::new).push(link);
+                }
+            }
+        }
+        let mut take = |role| by_role.remove(&role).unwrap_or_else(Vec::new);
+        let (sources, hubs, controllers) = (
+            take(LinkRole::Source),
+            take(LinkRole::Hub),
+            take(LinkRole::Controller),
+        );
+        if sources.is_empty() && hubs.is_empty() {
+            return Ok(());
+        }
+
+        // Energy sent this tick, which only arrives at the end of it
+        let mut incoming: HashMap<Id, u32> = HashMap::new();
+        let free = |link: &StructureLink, incoming: &HashMap<Id, u32>| {
+            let sent = incoming.get(&link.id()).cloned().unwrap_or(0);
+            link.energy_capacity().saturating_sub(link.energy() + sent)
+        };
+
+        let senders = sources
+            .iter()
+            .map(|s| (s, false))
+            .chain(hubs.iter().map(|h| (h, true)));
+        for (source, is_hub) in senders {
+            if source.cooldown() > 0 || source.energy() < LINK_SEND_MIN {
+                continue;
+            }
+            let receiver = controllers
+                .iter()
+                .chain(hubs.iter().filter(|_| !is_hub))
+                .map(|r| (r, free(r, &incoming)))
+                .find(|(_, space)| *space >= LINK_SEND_MIN);
+            let (receiver, space) = match receiver {
+                Some(receiver) => receiver,
+                None => continue,
+            };
+
+            let amount = source.energy().min(space);
+            match source.transfer_energy(receiver, Some(amount)) {
+                ReturnCode::Ok => {
+                    *incoming.entry(receiver.id()).or_insert(0) += amount;
+                }
+                ret => debug!(
+                    "{}: link at {} can't send to {}: {:?}",
+                    self.room.obj().name(),
+                    source.pos(),
+                    receiver.pos(),
+                    ret
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
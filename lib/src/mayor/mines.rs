@@ -0,0 +1,187 @@
+use super::{
+    planning::{terrain_of, tile},
+    Mayor,
+};
+use crate::{
+    error::Res,
+    terrain::{neighbours, range, Tile, UNREACHABLE},
+    Id,
+};
+use log::*;
+use screeps::{
+    find, look, HasId, HasPosition, Position, ResourceType, StructureProperties, StructureType,
+};
+use std::collections::HashMap;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// How often mines are checked for new containers and extractors.
+const MINE_REFRESH_INTERVAL: u32 = 100;
+
+/// A source or mineral and how it's worked.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Mine {
+    pub id: Id,
+    pub pos: Position,
+    /// Where the harvester stands and the container goes.
+    pub standing: Position,
+    /// Steps from the storage, or where it's planned to be, to `standing`.
+    pub path_length: u32,
+    #[serde(default)]
+    pub container: Option<Id>,
+    /// For minerals, the extractor on top of it.
+    #[serde(default)]
+    pub extractor: Option<Id>,
+}
+
+js_serializable!(Mine);
+js_deserializable!(Mine);
+
+impl<'a> Mayor<'a> {
+    /// Records the room's sources and minerals the first time they're seen,
+    /// and every so often looks for containers and extractors built on them.
+    pub fn update_mines(&mut self) -> Res<()> {
+        let room = self.room.obj();
+        let sources = room.find(find::SOURCES);
+        let minerals = room.find(find::MINERALS);
+        let known = self
+            .room
+            .memory()
+            .mines
+            .values()
+            .map(|m| m.len())
+            .sum::<usize>();
+
+        let time = screeps::game::time();
+        // Unreachable ones are only looked for again now and then
+        if known < sources.len() + minerals.len()
+            && (known == 0 || time % MINE_REFRESH_INTERVAL == 0)
+        {
+            let found = sources
+                .iter()
+                .map(|s| (ResourceType::Energy, s.id(), s.pos()))
+                .chain(minerals.iter().map(|m| (m.mineral_type(), m.id(), m.pos())))
+                .collect::<Vec<_>>();
+            self.discover_mines(found);
+        } else if time % MINE_REFRESH_INTERVAL == 0 {
+            self.refresh_mines();
+        }
+
+        Ok(())
+    }
+
+    fn discover_mines(&mut self, found: Vec<(ResourceType, Id, Position)>) {
+        let room = self.room.obj();
+        let name = room.name();
+        let hub = match self.hub() {
+            Some(hub) => hub,
+            None => return,
+        };
+        let terrain = terrain_of(room);
+        let from_hub = terrain.flood_fill(&[hub], |_| true);
+        let planned_spots = self
+            .room
+            .memory()
+            .layout
+            .as_ref()
+            .map_or(vec![], |l| l.source_spots.clone());
+
+        let mut mines: HashMap<ResourceType, Vec<Mine>> = HashMap::new();
+        for (resource, id, pos) in found {
+            let target = tile(&pos);
+            // Stick to the layout where it has a spot, so the link ends up
+            // next to the harvester
+            let standing = planned_spots
+                .iter()
+                .find(|s| range(**s, target) == 1)
+                .cloned()
+                .or_else(|| {
+                    neighbours(target)
+                        .filter(|t| !terrain.is_wall(*t))
+                        .min_by_key(|t| from_hub[*t])
+                })
+                .filter(|t| from_hub[*t] != UNREACHABLE);
+            let standing = match standing {
+                Some(standing) => standing,
+                None => {
+                    warn!("{}: can't reach the {:?} at {}", name, resource, pos);
+                    continue;
+                }
+            };
+
+            let standing_pos = Position::new(standing.0.into(), standing.1.into(), name);
+            let mine = Mine {
+                id,
+                pos,
+                standing: standing_pos,
+                path_length: from_hub[standing],
+                container: self.structure_at(&standing_pos, StructureType::Container),
+                extractor: self.structure_at(&pos, StructureType::Extractor),
+            };
+            info!(
+                "{}: found {:?} mine at {}, {} steps out",
+                name, resource, pos, mine.path_length
+            );
+            mines.entry(resource).or_insert_with(Vec::new).push(mine);
+        }
+
+        self.room.memory_mut().mines = mines;
+    }
+
+    /// Picks up containers and extractors built since the last look, and
+    /// forgets destroyed ones.
+    fn refresh_mines(&mut self) {
+        let mines = self.room.memory().mines.clone();
+        let mut refreshed = HashMap::new();
+        for (resource, mut list) in mines {
+            for mine in list.iter_mut() {
+                mine.container = self.structure_at(&mine.standing, StructureType::Container);
+                if resource != ResourceType::Energy {
+                    mine.extractor = self.structure_at(&mine.pos, StructureType::Extractor);
+                }
+            }
+            refreshed.insert(resource, list);
+        }
+
+        let changed = refreshed.iter().any(|(resource, list)| {
+            let old = &self.room.memory().mines[resource];
+            list.iter()
+                .zip(old.iter())
+                .any(|(new, old)| new.container != old.container || new.extractor != old.extractor)
+        });
+        if changed {
+            self.room.memory_mut().mines = refreshed;
+        }
+    }
+
+    /// Where the room's resources are taken to: the storage, where it's
+    /// planned to go, or failing that the first spawn.
+    fn hub(&self) -> Option<Tile> {
+        let room = self.room.obj();
+        if let Some(storage) = room.storage() {
+            return Some(tile(&storage.pos()));
+        }
+        if let Some(layout) = &self.room.memory().layout {
+            return Some(layout.anchor);
+        }
+        room.find(find::MY_SPAWNS).first().map(|s| tile(&s.pos()))
+    }
+
+    fn structure_at(&self, pos: &Position, structure_type: StructureType) -> Option<Id> {
+        self.room
+            .obj()
+            .look_for_at(look::STRUCTURES, pos)
+            .iter()
+            .find(|s| s.structure_type() == structure_type)
+            .map(|s| s.id())
+    }
+
+    /// The mine for a source or mineral, if it's been discovered.
+    pub fn mine(&self, id: &Id) -> Option<&Mine> {
+        self.room
+            .memory()
+            .mines
+            .values()
+            .flat_map(|m| m.iter())
+            .find(|m| &m.id == id)
+    }
+}
@@ -0,0 +1,109 @@
+use super::Mayor;
+use crate::{
+    error::Res,
+    planner::{self, PlanInput, PlannedStructure},
+    terrain::{Cell, Terrain, Tile},
+};
+use log::*;
+use screeps::{
+    find, HasPosition, Position, Room as ScreepsRoom, StructureType, Terrain as TerrainKind,
+};
+
+/// How often a room that didn't fit a layout is tried again.
+const REPLAN_INTERVAL: u32 = 100;
+
+impl<'a> Mayor<'a> {
+    /// Plans the room's base the first time the Mayor runs it, and keeps
+    /// `extension_spots` and `forts` in line with it. Layouts saved before
+    /// ramparts were planned get them added.
+    pub fn plan_layout(&mut self) -> Res<()> {
+        if let Some(fortified) = self.room.memory().layout.as_ref().map(|l| l.fortified) {
+            if !fortified {
+                self.fortify_layout();
+            }
+            return Ok(());
+        }
+        // Planning is expensive, so a room that failed isn't retried every tick
+        if self.room.memory().layout_failed && screeps::game::time() % REPLAN_INTERVAL != 0 {
+            return Ok(());
+        }
+
+        let room = self.room.obj();
+        let name = room.name();
+        let controller = match room.controller() {
+            Some(controller) => tile(&controller.pos()),
+            None => return Ok(()),
+        };
+        let terrain = terrain_of(room);
+        let input = PlanInput {
+            terrain: &terrain,
+            controller,
+            sources: room
+                .find(find::SOURCES)
+                .iter()
+                .map(|s| tile(&s.pos()))
+                .collect(),
+            mineral: room.find(find::MINERALS).first().map(|m| tile(&m.pos())),
+            spawn: room.find(find::MY_SPAWNS).first().map(|s| tile(&s.pos())),
+        };
+
+        match planner::plan(&input) {
+            Some(layout) => {
+                info!(
+                    "{}: planned {} structures around {:?}",
+                    name,
+                    layout.structures.len(),
+                    layout.anchor
+                );
+                let memory = self.room.memory_mut();
+                let position = |s: &PlannedStructure| Position::new(s.x.into(), s.y.into(), name);
+                memory.extension_spots = layout
+                    .of_type(StructureType::Extension)
+                    .map(position)
+                    .collect();
+                memory.forts = layout
+                    .of_type(StructureType::Rampart)
+                    .map(position)
+                    .collect();
+                memory.layout = Some(layout);
+                memory.layout_failed = false;
+            }
+            None => {
+                warn!("{}: no room for a base layout", name);
+                self.room.memory_mut().layout_failed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds ramparts to a layout planned without them.
+    fn fortify_layout(&mut self) {
+        let name = self.room.obj().name();
+        let terrain = terrain_of(self.room.obj());
+        let memory = self.room.memory_mut();
+        let layout = match memory.layout.as_mut() {
+            Some(layout) => layout,
+            None => return,
+        };
+        planner::add_ramparts(layout, &terrain);
+        memory.forts = layout
+            .of_type(StructureType::Rampart)
+            .map(|s| Position::new(s.x.into(), s.y.into(), name))
+            .collect();
+        info!("{}: planned {} ramparts", name, memory.forts.len());
+    }
+}
+
+pub fn tile(pos: &Position) -> Tile {
+    (pos.x() as u8, pos.y() as u8)
+}
+
+pub fn terrain_of(room: &ScreepsRoom) -> Terrain {
+    let terrain = room.get_terrain();
+    Terrain::new(|(x, y)| match terrain.get(x.into(), y.into()) {
+        TerrainKind::Plain => Cell::Plain,
+        TerrainKind::Swamp => Cell::Swamp,
+        TerrainKind::Wall => Cell::Wall,
+    })
+}
@@ -0,0 +1,129 @@
+use super::Mayor;
+use crate::creeps::Role;
+use screeps::{constants::Part, find, HasPosition, HasStore, ResourceType};
+
+const ENERGY_REGEN_TIME: u32 = 300;
+const HARVEST_POWER: u32 = 2;
+const CARRY_CAPACITY: u32 = 50;
+/// Construction progress one builder is expected to handle.
+const BUILD_PER_BUILDER: u32 = 10_000;
+const MAX_BUILDERS: usize = 3;
+/// Storage energy kept back before extra upgraders are spawned.
+const STORAGE_RESERVE: u32 = 100_000;
+/// Storage energy above the reserve that pays for one more upgrader.
+const STORAGE_PER_UPGRADER: u32 = 50_000;
+const MAX_UPGRADERS: usize = 5;
+
+impl<'a> Mayor<'a> {
+    /// The `(role, equip)` entries this room should have alive, one per
+    /// creep.
+    ///
+    /// Rooms listed in `auto_population` derive their counts from the room,
+    /// with any role in `roles_to_spawn` overriding the derived count.
+    /// Other rooms use `roles_to_spawn` as is.
+    pub fn population_targets(&self) -> Option<Vec<(Role, String)>> {
+        let room_name = self.room.obj().name().to_array_string();
+        let config = self.config.memory();
+        let listed = config.roles_to_spawn.get(room_name.as_str());
+
+        if !config
+            .auto_population
+            .iter()
+            .any(|r| r == room_name.as_str())
+        {
+            return listed.cloned();
+        }
+
+        let mut targets = listed.cloned().unwrap_or_default();
+        for role in &[
+            Role::Harvester,
+            Role::Transporter,
+            Role::Builder,
+            Role::Upgrader,
+        ] {
+            if targets.iter().any(|(r, _)| r == role) {
+                continue;
+            }
+            let equip = match config.role_equip.get(role) {
+                Some(equip) => equip,
+                None => continue,
+            };
+            for _ in 0..self.derived_count(*role, equip) {
+                targets.push((*role, equip.clone()));
+            }
+        }
+
+        Some(targets)
+    }
+
+    fn derived_count(&self, role: Role, equip: &str) -> usize {
+        let room = self.room.obj();
+        let parts = |part| {
+            self.template(equip)
+                .and_then(|t| t.scale(room.energy_capacity_available()))
+                .map_or(0, |b| b.iter().filter(|p| **p == part).count() as u32)
+        };
+
+        match role {
+            Role::Harvester => {
+                let work = parts(Part::Work);
+                if work == 0 {
+                    return 0;
+                }
+                room.find(find::SOURCES)
+                    .iter()
+                    .map(|s| {
+                        let needed = s.energy_capacity() / ENERGY_REGEN_TIME / HARVEST_POWER;
+                        div_ceil(needed, work) as usize
+                    })
+                    .sum()
+            }
+            Role::Transporter => {
+                let carry = parts(Part::Carry);
+                if carry == 0 {
+                    return 0;
+                }
+                let drop_off = match room.storage() {
+                    Some(storage) => storage.pos(),
+                    None => match room.find(find::MY_SPAWNS).first() {
+                        Some(spawn) => spawn.pos(),
+                        None => return 0,
+                    },
+                };
+                let needed: u32 = room
+                    .find(find::SOURCES)
+                    .iter()
+                    .map(|s| {
+                        let per_tick = s.energy_capacity() / ENERGY_REGEN_TIME;
+                        let distance = self
+                            .mine(&s.id())
+                            .map_or_else(|| s.pos().get_range_to(&drop_off), |m| m.path_length);
+                        let round_trip = 2 * distance;
+                        div_ceil(per_tick * round_trip, CARRY_CAPACITY)
+                    })
+                    .sum();
+                div_ceil(needed, carry) as usize
+            }
+            Role::Builder => {
+                let backlog: u32 = room
+                    .find(find::MY_CONSTRUCTION_SITES)
+                    .iter()
+                    .map(|s| s.progress_total() - s.progress())
+                    .sum();
+                (div_ceil(backlog, BUILD_PER_BUILDER) as usize).min(MAX_BUILDERS)
+            }
+            Role::Upgrader => {
+                let stored = room
+                    .storage()
+                    .map_or(0, |s| s.store_of(ResourceType::Energy));
+                let surplus = stored.saturating_sub(STORAGE_RESERVE);
+                (1 + (surplus / STORAGE_PER_UPGRADER) as usize).min(MAX_UPGRADERS)
+            }
+            Role::Generic | Role::Attacker | Role::Claimer => 0,
+        }
+    }
+}
+
+fn div_ceil(a: u32, b: u32) -> u32 {
+    (a + b - 1) / b
+}
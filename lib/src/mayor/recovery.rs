@@ -0,0 +1,166 @@
+use super::{Mayor, Priority, Requester, SpawnRequest};
+use crate::{
+    creeps::{Action, BodyTemplate, Creep, Movement, Role},
+    error::Res,
+};
+use log::*;
+use screeps::{
+    constants::Part, find, HasId, HasPosition, ResourceType, StructureProperties, StructureType,
+};
+use std::collections::VecDeque;
+
+/// Name of the `equip` entry used for recovery creeps. If the config doesn't
+/// have one, `recovery_template` is used.
+pub const RECOVERY_EQUIP: &str = "recovery";
+
+/// How many generic creeps to keep around while recovering.
+const RECOVERY_CREEPS: usize = 2;
+
+pub fn recovery_template() -> BodyTemplate {
+    BodyTemplate {
+        prefix: vec![],
+        segment: vec![Part::Work, Part::Carry],
+        suffix: vec![],
+        max_size: 50,
+        max_segments: Some(3),
+        movement: Movement::Plain,
+    }
+}
+
+impl<'a> Mayor<'a> {
+    /// Enters recovery mode when the room can no longer refill its spawns,
+    /// and leaves it once harvesters and transporters are back.
+    pub fn check_recovery(&mut self, creeps: &[&mut Creep]) -> Res<()> {
+        let count = |role| creeps.iter().filter(|c| c.memory().role == role).count();
+        let harvesters = count(Role::Harvester);
+        let transporters = count(Role::Transporter);
+        let generics = count(Role::Generic);
+        let energy = self.room.obj().energy_available();
+
+        if self.room.memory().recovering {
+            let full = energy >= self.room.obj().energy_capacity_available();
+            if (harvesters > 0 && transporters > 0) || full {
+                info!("{} has recovered", self.room.obj().name());
+                self.room.memory_mut().recovering = false;
+            }
+        } else if harvesters + generics == 0 || transporters + generics == 0 {
+            let cheapest = self.cheapest_body();
+            if cheapest.map_or(false, |c| energy < c) {
+                warn!(
+                    "{} is entering recovery: {} energy, cheapest body costs {}",
+                    self.room.obj().name(),
+                    energy,
+                    cheapest.unwrap_or(0)
+                );
+                self.room.memory_mut().recovering = true;
+            }
+        }
+
+        if !self.room.memory().recovering {
+            return Ok(());
+        }
+
+        let queued = self
+            .room
+            .memory()
+            .spawn_queue
+            .iter()
+            .filter(|r| r.equip == RECOVERY_EQUIP)
+            .count();
+        if generics + queued < RECOVERY_CREEPS {
+            let request = SpawnRequest::new(
+                Role::Generic,
+                RECOVERY_EQUIP,
+                Priority::Critical,
+                Requester::Mayor,
+            );
+            self.request_spawn(request)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps idle generic creeps harvesting and filling spawns and
+    /// extensions, upgrading the controller when those are full. Targets
+    /// are reserved like the job board's, so creeps don't pile onto one
+    /// spawn.
+    pub fn direct_recovery_creeps(&mut self, creeps: &mut [&mut Creep]) -> Res<()> {
+        if !self.room.memory().recovering {
+            return Ok(());
+        }
+        self.room.memory_mut().reservations.refresh(creeps);
+
+        let room = self.room.obj().clone();
+        for creep in creeps.iter_mut() {
+            if creep.memory().role != Role::Generic || !creep.memory().actions.is_empty() {
+                continue;
+            }
+
+            let pos = creep.obj().pos();
+            let energy = creep.obj().carry_of(ResourceType::Energy);
+            let reservations = &self.room.memory().reservations;
+            let action = if energy == 0 {
+                room.find(find::SOURCES_ACTIVE)
+                    .into_iter()
+                    .min_by_key(|s| pos.get_range_to(&s.pos()))
+                    .map(|s| Action::Harvest { target_id: s.id() })
+            } else {
+                room.find(find::MY_STRUCTURES)
+                    .into_iter()
+                    .filter(|s| match s.structure_type() {
+                        StructureType::Spawn | StructureType::Extension => true,
+                        _ => false,
+                    })
+                    .filter(|s| {
+                        s.as_can_store_energy().map_or(false, |e| {
+                            e.energy() + reservations.amount(&s.id()) < e.energy_capacity()
+                        })
+                    })
+                    .min_by_key(|s| pos.get_range_to(&s.pos()))
+                    .map(|s| Action::TransferAll {
+                        target_id: s.id(),
+                        resource: ResourceType::Energy,
+                    })
+                    .or_else(|| {
+                        room.controller()
+                            .map(|c| Action::ControllerUpgrade { target_id: c.id() })
+                    })
+            };
+
+            if let Some(action) = action {
+                if let Action::TransferAll { target_id, .. } = &action {
+                    let name = creep.obj().name();
+                    self.room
+                        .memory_mut()
+                        .reservations
+                        .reserve(target_id, &name, energy);
+                }
+                creep.memory_mut().actions = VecDeque::from(vec![action]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cost of the cheapest body this room normally spawns, going by its
+    /// population targets, or by `role_equip` when it has none yet.
+    fn cheapest_body(&self) -> Option<u32> {
+        let equips = match self.population_targets() {
+            Some(targets) if !targets.is_empty() => {
+                targets.into_iter().map(|(_, equip)| equip).collect()
+            }
+            _ => self
+                .config
+                .memory()
+                .role_equip
+                .values()
+                .cloned()
+                .collect::<Vec<_>>(),
+        };
+        equips
+            .iter()
+            .filter_map(|equip| self.template(equip))
+            .map(|t| t.minimum_cost())
+            .min()
+    }
+}
@@ -0,0 +1,225 @@
+use super::{construction::is_ours, planning::tile, Mayor};
+use crate::{
+    creeps::{Action, Creep},
+    error::Res,
+    planner::fortify_target,
+    terrain::Tile,
+    Id,
+};
+use log::*;
+use screeps::{find, HasId, HasPosition, Position, StructureProperties, StructureType};
+use std::collections::HashSet;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// How often the room is searched for damaged structures.
+const REPAIR_INTERVAL: u32 = 10;
+/// Roads and containers decay all the time, so they're left until they're
+/// down to this share of their hits rather than topped up after every tick.
+const DECAYING_REPAIR_BELOW: f64 = 0.6;
+/// Other structures are repaired below this share of their hits.
+const REPAIR_BELOW: f64 = 0.9;
+/// Below this share of their hits roads and containers are close to
+/// falling apart.
+const DECAYING_CRITICAL: f64 = 0.2;
+/// Ramparts with fewer hits than this are close to decaying away.
+pub(super) const RAMPART_CRITICAL: u32 = 5000;
+
+/// A damaged structure, and whether it's worth fixing before anything else.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RepairTask {
+    pub target_id: Id,
+    pub pos: Position,
+    /// Walls and ramparts, built up to `fortify_target` rather than their
+    /// maximum.
+    pub fortify: bool,
+    /// About to be lost.
+    pub critical: bool,
+}
+
+js_serializable!(RepairTask);
+js_deserializable!(RepairTask);
+
+impl<'a> Mayor<'a> {
+    /// Every so often ranks the room's damaged structures into
+    /// `RoomMemory::repairs`, most urgent first. Structures that aren't part
+    /// of the plan or are about to be dismantled are blacklisted instead.
+    /// Anything on `repair_blacklist`, however it got there, stays there
+    /// until it's gone.
+    pub fn update_repairs(&mut self, creeps: &[&mut Creep]) -> Res<()> {
+        if screeps::game::time() % REPAIR_INTERVAL != 0 {
+            return Ok(());
+        }
+
+        let room = self.room.obj();
+        let rcl = room.controller().map_or(0, |c| c.level());
+        let planned = self.planned_structures();
+        let dismantling = creeps
+            .iter()
+            .flat_map(|c| c.memory().actions.iter())
+            .filter_map(|a| match a {
+                Action::Dismantle { target_id } => Some(target_id.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let mut blacklist = self.room.memory().repair_blacklist.clone();
+        let mut ranked = vec![];
+        let structures = room.find(find::STRUCTURES);
+        for structure in &structures {
+            let id = structure.id();
+            if !is_ours(structure) || blacklist.contains(&id) {
+                continue;
+            }
+            let structure_type = structure.structure_type();
+            // Controllers and anything else the layout doesn't place are left be
+            let unplanned = is_planned_type(structure_type)
+                && planned.as_ref().map_or(false, |p| {
+                    !p.contains(&(structure_type, tile(&structure.pos())))
+                });
+            if unplanned || dismantling.contains(&id) {
+                debug!(
+                    "{}: not repairing the {:?} at {}",
+                    room.name(),
+                    structure_type,
+                    structure.pos()
+                );
+                blacklist.push(id);
+                continue;
+            }
+
+            let (hits, hits_max) = match structure.as_attackable() {
+                Some(a) if a.hits_max() > 0 => (a.hits(), a.hits_max()),
+                _ => continue,
+            };
+            let fortify = match structure_type {
+                StructureType::Rampart | StructureType::Wall => true,
+                _ => false,
+            };
+            let (share, critical) = if fortify {
+                let target = fortify_target(rcl).min(hits_max);
+                if target == 0 || hits >= target {
+                    continue;
+                }
+                let critical = structure_type == StructureType::Rampart && hits < RAMPART_CRITICAL;
+                (f64::from(hits) / f64::from(target), critical)
+            } else {
+                let share = f64::from(hits) / f64::from(hits_max);
+                let decaying = match structure_type {
+                    StructureType::Road | StructureType::Container => true,
+                    _ => false,
+                };
+                let below = if decaying {
+                    DECAYING_REPAIR_BELOW
+                } else {
+                    REPAIR_BELOW
+                };
+                if share >= below {
+                    continue;
+                }
+                (share, decaying && share < DECAYING_CRITICAL)
+            };
+
+            let task = RepairTask {
+                target_id: id,
+                pos: structure.pos(),
+                fortify,
+                critical,
+            };
+            ranked.push((share, task));
+        }
+
+        // Critical first, then repairs before fortifying, then the most
+        // damaged
+        ranked.sort_by(|(a_share, a), (b_share, b)| {
+            (!a.critical, a.fortify)
+                .cmp(&(!b.critical, b.fortify))
+                .then(
+                    a_share
+                        .partial_cmp(b_share)
+                        .unwrap_or(std::cmp::Ordering::Equal),
+                )
+        });
+
+        // Forget blacklisted structures that are gone
+        let existing = structures.iter().map(|s| s.id()).collect::<HashSet<_>>();
+        blacklist.retain(|id| existing.contains(id));
+
+        let memory = self.room.memory_mut();
+        memory.repairs = ranked.into_iter().map(|(_, task)| task).collect();
+        memory.repair_blacklist = blacklist;
+
+        Ok(())
+    }
+
+    /// Repairs for creeps to do. Where there are towers they take care of
+    /// everything but fortifying, which would drain them.
+    pub fn creep_repairs(&self) -> impl Iterator<Item = &RepairTask> {
+        let towers = self.has_towers();
+        self.room
+            .memory()
+            .repairs
+            .iter()
+            .filter(move |r| !towers || r.fortify)
+    }
+
+    /// Repairs for towers to do, most urgent first. They only fortify what's
+    /// about to be lost.
+    pub fn tower_repairs(&self) -> impl Iterator<Item = &RepairTask> {
+        self.room
+            .memory()
+            .repairs
+            .iter()
+            .filter(|r| !r.fortify || r.critical)
+    }
+
+    fn has_towers(&self) -> bool {
+        self.room
+            .obj()
+            .find(find::MY_STRUCTURES)
+            .iter()
+            .any(|s| s.structure_type() == StructureType::Tower)
+    }
+
+    /// What the room is meant to have where, or `None` if it has no layout
+    /// to go by.
+    pub(super) fn planned_structures(&self) -> Option<HashSet<(StructureType, Tile)>> {
+        let memory = self.room.memory();
+        let layout = memory.layout.as_ref()?;
+        let mut planned = layout
+            .structures
+            .iter()
+            .map(|s| (s.structure, s.tile()))
+            .collect::<HashSet<_>>();
+        planned.extend(
+            memory
+                .roads
+                .iter()
+                .map(|pos| (StructureType::Road, tile(pos))),
+        );
+        planned.extend(
+            memory
+                .mines
+                .values()
+                .flat_map(|m| m.iter())
+                .map(|m| (StructureType::Container, tile(&m.standing))),
+        );
+        planned.extend(
+            memory
+                .mines
+                .values()
+                .flat_map(|m| m.iter())
+                .map(|m| (StructureType::Extractor, tile(&m.pos))),
+        );
+        Some(planned)
+    }
+}
+
+/// Structure types the layout decides the place of.
+fn is_planned_type(structure_type: StructureType) -> bool {
+    use StructureType::*;
+    match structure_type {
+        Spawn | Extension | Road | Wall | Rampart | Link | Storage | Tower | Extractor | Lab
+        | Terminal | Container => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    creeps::{Action, Creep, Role},
+    Id,
+};
+use std::collections::HashMap;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reservation {
+    pub creep: String,
+    #[serde(default)]
+    pub amount: u32,
+}
+
+js_serializable!(Reservation);
+js_deserializable!(Reservation);
+
+/// Which creeps have claimed which targets, and for how much, so that job
+/// assignment doesn't send several creeps after the same thing.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Reservations {
+    targets: HashMap<Id, Vec<Reservation>>,
+}
+
+impl Reservations {
+    pub fn reserve(&mut self, target: &Id, creep: &str, amount: u32) {
+        let reservations = self.targets.entry(target.clone()).or_insert_with(Vec::new);
+        match reservations.iter_mut().find(|r| r.creep == creep) {
+            Some(reservation) => reservation.amount += amount,
+            None => reservations.push(Reservation {
+                creep: creep.to_string(),
+                amount,
+            }),
+        }
+    }
+
+    /// Number of creeps holding a reservation on `target`.
+    pub fn count(&self, target: &Id) -> usize {
+        self.targets.get(target).map_or(0, |r| r.len())
+    }
+
+    /// Total amount reserved on `target`.
+    pub fn amount(&self, target: &Id) -> u32 {
+        self.targets
+            .get(target)
+            .map_or(0, |r| r.iter().map(|r| r.amount).sum())
+    }
+
+    pub fn release(&mut self, target: &Id, creep: &str) {
+        if let Some(reservations) = self.targets.get_mut(target) {
+            reservations.retain(|r| r.creep != creep);
+            if reservations.is_empty() {
+                self.targets.remove(target);
+            }
+        }
+    }
+
+    /// Moves everything `from` has reserved over to `to`.
+    pub fn hand_over(&mut self, from: &str, to: &str) {
+        let held = self
+            .targets
+            .iter()
+            .filter_map(|(target, reservations)| {
+                reservations
+                    .iter()
+                    .find(|r| r.creep == from)
+                    .map(|r| (target.clone(), r.amount))
+            })
+            .collect::<Vec<_>>();
+        for (target, amount) in held {
+            self.release(&target, from);
+            self.reserve(&target, to, amount);
+        }
+    }
+
+    /// Drops reservations held by creeps that are gone or no longer have an
+    /// action queued on the target, and adds them for creeps that were given
+    /// actions some other way than through the job board.
+    pub fn refresh(&mut self, creeps: &[&mut Creep]) {
+        let queued = creeps
+            .iter()
+            .map(|c| {
+                let targets = c
+                    .memory()
+                    .actions
+                    .iter()
+                    .filter_map(|a| a.target_id())
+                    .collect::<Vec<_>>();
+                (c.obj().name(), targets)
+            })
+            .collect::<HashMap<_, _>>();
+
+        for (target, reservations) in self.targets.iter_mut() {
+            reservations.retain(|r| {
+                queued
+                    .get(&r.creep)
+                    .map_or(false, |targets| targets.contains(&target))
+            });
+        }
+        self.targets.retain(|_, r| !r.is_empty());
+
+        for creep in creeps {
+            let name = creep.obj().name();
+            for action in creep.memory().actions.iter() {
+                // Other creeps harvesting for themselves don't take a
+                // harvester's place
+                if let Action::Harvest { .. } = action {
+                    if creep.memory().role != Role::Harvester {
+                        continue;
+                    }
+                }
+                let target = match action.target_id() {
+                    Some(target) => target,
+                    None => continue,
+                };
+                let held = self
+                    .targets
+                    .get(target)
+                    .map_or(false, |r| r.iter().any(|r| r.creep == name));
+                if !held {
+                    self.reserve(target, &name, queued_amount(creep, action));
+                }
+            }
+        }
+    }
+}
+
+/// How much of its target a queued action takes up.
+fn queued_amount(creep: &Creep, action: &Action) -> u32 {
+    let free = creep.obj().carry_capacity() - creep.obj().carry_total();
+    match action {
+        Action::TransferAmount { amount, .. } | Action::WithdrawAmount { amount, .. } => *amount,
+        Action::TransferAll { resource, .. } => creep.obj().carry_of(*resource),
+        Action::WithdrawAll { .. } | Action::Pickup { .. } => free,
+        _ => 0,
+    }
+}
@@ -0,0 +1,187 @@
+use super::{
+    planning::{terrain_of, tile},
+    Mayor,
+};
+use crate::{
+    creeps::Creep,
+    error::Res,
+    planner::Layout,
+    terrain::{index, is_edge, Tile, ROOM_SIZE},
+};
+use log::*;
+use screeps::{
+    pathfinder::{self, LocalCostMatrix, SearchOptions},
+    HasPosition, Position, StructureType,
+};
+use std::collections::{HashMap, HashSet};
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+/// How often roads are planned again, to pick up changes in traffic.
+const ROAD_REPLAN_INTERVAL: u32 = 5000;
+/// Traffic counts are halved this often, so routes nobody uses fade out.
+const TRAFFIC_DECAY_INTERVAL: u32 = 1500;
+/// Tiles walked over at least this often are costed like roads.
+const BUSY_TILE: u32 = 100;
+/// Creeps further than this from their work site count as travelling.
+const TRAVELLING_RANGE: u32 = 3;
+
+const ROAD_COST: u8 = 1;
+const PLAIN_COST: u8 = 2;
+const SWAMP_COST: u8 = 10;
+const BLOCKED: u8 = 255;
+
+/// How often our creeps have walked over each tile of a room.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Traffic {
+    counts: HashMap<u16, u32>,
+}
+
+js_serializable!(Traffic);
+js_deserializable!(Traffic);
+
+impl Traffic {
+    pub fn record(&mut self, tile: Tile) {
+        *self.counts.entry(index(tile) as u16).or_insert(0) += 1;
+    }
+
+    pub fn get(&self, tile: Tile) -> u32 {
+        self.counts.get(&(index(tile) as u16)).cloned().unwrap_or(0)
+    }
+
+    pub fn decay(&mut self) {
+        for count in self.counts.values_mut() {
+            *count /= 2;
+        }
+        self.counts.retain(|_, count| *count > 0);
+    }
+
+    /// Tiles walked over at least `threshold` times.
+    pub fn busy(&self, threshold: u32) -> impl Iterator<Item = Tile> + '_ {
+        self.counts
+            .iter()
+            .filter(move |(_, count)| **count >= threshold)
+            .map(|(i, _)| {
+                let i = *i as usize;
+                ((i % ROOM_SIZE) as u8, (i / ROOM_SIZE) as u8)
+            })
+    }
+}
+
+impl<'a> Mayor<'a> {
+    /// Counts where travelling creeps are, for the road planner.
+    pub fn record_traffic(&mut self, creeps: &[&mut Creep]) {
+        let name = self.room.obj().name();
+        let time = screeps::game::time();
+        let tiles = creeps
+            .iter()
+            .filter(|c| !c.obj().spawning() && c.obj().pos().room_name() == name)
+            .filter(|c| c.obj().pos().get_range_to(&c.work_site()) > TRAVELLING_RANGE)
+            .map(|c| tile(&c.obj().pos()))
+            .collect::<Vec<_>>();
+        let decay = time % TRAFFIC_DECAY_INTERVAL == 0;
+        if tiles.is_empty() && !decay {
+            return;
+        }
+
+        let traffic = &mut self.room.memory_mut().traffic;
+        for t in tiles {
+            traffic.record(t);
+        }
+        if decay {
+            traffic.decay();
+        }
+    }
+
+    /// Plans roads from the storage to every mine, the controller and the
+    /// exits. Each route is costed with the roads planned before it, so they
+    /// share as much road as they can.
+    pub fn plan_roads(&mut self) -> Res<()> {
+        let memory = self.room.memory();
+        let layout = match &memory.layout {
+            Some(layout) => layout,
+            None => return Ok(()),
+        };
+        let time = screeps::game::time();
+        let planned = memory.roads_planned;
+        if (planned != 0 && time < planned + ROAD_REPLAN_INTERVAL) || memory.mines.is_empty() {
+            return Ok(());
+        }
+
+        let room = self.room.obj();
+        let name = room.name();
+        let position = |(x, y): Tile| Position::new(x.into(), y.into(), name);
+        let origin = position(layout.anchor);
+
+        let mut goals = memory
+            .mines
+            .values()
+            .flat_map(|m| m.iter())
+            .map(|m| (m.standing, 0))
+            .collect::<Vec<_>>();
+        match (layout.upgrade_spot, room.controller()) {
+            (Some(spot), _) => goals.push((position(spot), 0)),
+            (None, Some(controller)) => goals.push((controller.pos(), 3)),
+            (None, None) => {}
+        }
+        for exit in terrain_of(room).exits() {
+            goals.push((position(exit.tiles[exit.tiles.len() / 2]), 0));
+        }
+
+        let mut matrix = self.road_matrix(layout);
+        let layout_roads = layout
+            .of_type(StructureType::Road)
+            .map(|s| s.tile())
+            .collect::<HashSet<_>>();
+        let mut roads = HashSet::new();
+        for (goal, range) in goals {
+            let options = SearchOptions::new()
+                .room_callback(|_| matrix.upload())
+                .plain_cost(PLAIN_COST)
+                .swamp_cost(SWAMP_COST)
+                .max_rooms(1);
+            let result = pathfinder::search(&origin, &goal, range, options);
+            if result.incomplete {
+                debug!("{}: no road to {}", name, goal);
+                continue;
+            }
+
+            for pos in result.load_local_path() {
+                let t = tile(&pos);
+                if pos.room_name() != name || is_edge(t) {
+                    continue;
+                }
+                matrix.set(t.0, t.1, ROAD_COST);
+                if !layout_roads.contains(&t) {
+                    roads.insert(t);
+                }
+            }
+        }
+
+        info!("{}: planned {} road tiles", name, roads.len());
+        let roads = roads.into_iter().map(position).collect();
+        let memory = self.room.memory_mut();
+        memory.roads = roads;
+        memory.roads_planned = time;
+
+        Ok(())
+    }
+
+    /// Planned roads and busy tiles are cheap, everything else the layout
+    /// builds on can't be walked.
+    fn road_matrix(&self, layout: &Layout) -> LocalCostMatrix {
+        let mut matrix = LocalCostMatrix::new();
+        for (x, y) in self.room.memory().traffic.busy(BUSY_TILE) {
+            matrix.set(x, y, ROAD_COST);
+        }
+        for structure in &layout.structures {
+            let cost = match structure.structure {
+                StructureType::Road => ROAD_COST,
+                StructureType::Rampart | StructureType::Container => continue,
+                _ => BLOCKED,
+            };
+            matrix.set(structure.x, structure.y, cost);
+        }
+        matrix
+    }
+}
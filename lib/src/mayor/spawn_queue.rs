@@ -0,0 +1,155 @@
+use crate::creeps::{Action, Role};
+use screeps::Position;
+use std::collections::VecDeque;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Priority {
+    #[serde(rename = "L")]
+    Low,
+    #[serde(rename = "N")]
+    Normal,
+    #[serde(rename = "H")]
+    High,
+    #[serde(rename = "C")]
+    Critical,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Who asked for a creep, so subsystems can tell their requests apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Requester {
+    #[serde(rename = "M")]
+    Mayor,
+    #[serde(rename = "D")]
+    Defense,
+    #[serde(rename = "R")]
+    Remote,
+    #[serde(rename = "E")]
+    Expansion,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpawnRequest {
+    pub role: Role,
+    pub equip: String,
+    #[serde(default)]
+    pub priority: Priority,
+    pub requester: Requester,
+    #[serde(default)]
+    pub actions: VecDeque<Action>,
+    /// Where the creep will work, used to pick the closest spawn.
+    #[serde(default)]
+    pub destination: Option<Position>,
+    /// The room the creep belongs to, if not the one spawning it.
+    #[serde(default)]
+    pub home: Option<String>,
+    /// A dying creep whose work this one takes over.
+    #[serde(default)]
+    pub replaces: Option<String>,
+    pub created: u32,
+    #[serde(default)]
+    pub expires: Option<u32>,
+}
+
+js_serializable!(SpawnRequest);
+js_deserializable!(SpawnRequest);
+
+impl SpawnRequest {
+    pub fn new(role: Role, equip: &str, priority: Priority, requester: Requester) -> Self {
+        Self {
+            role,
+            equip: equip.to_string(),
+            priority,
+            requester,
+            actions: VecDeque::new(),
+            destination: None,
+            home: None,
+            replaces: None,
+            created: screeps::game::time(),
+            expires: None,
+        }
+    }
+
+    pub fn actions(mut self, actions: VecDeque<Action>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    pub fn destination(mut self, pos: Position) -> Self {
+        self.destination = Some(pos);
+        self
+    }
+
+    pub fn home(mut self, room: &str) -> Self {
+        self.home = Some(room.to_string());
+        self
+    }
+
+    pub fn replaces(mut self, name: &str) -> Self {
+        self.replaces = Some(name.to_string());
+        self
+    }
+
+    pub fn expires_in(mut self, ticks: u32) -> Self {
+        self.expires = Some(self.created + ticks);
+        self
+    }
+
+    pub fn is_expired(&self, time: u32) -> bool {
+        self.expires.map_or(false, |e| time >= e)
+    }
+}
+
+/// Pending spawns for a room, highest priority first and oldest first within
+/// a priority.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SpawnQueue {
+    requests: Vec<SpawnRequest>,
+}
+
+impl SpawnQueue {
+    pub fn push(&mut self, request: SpawnRequest) {
+        let index = self
+            .requests
+            .iter()
+            .position(|r| r.priority < request.priority)
+            .unwrap_or_else(|| self.requests.len());
+        self.requests.insert(index, request);
+    }
+
+    pub fn front(&self) -> Option<&SpawnRequest> {
+        self.requests.first()
+    }
+
+    pub fn pop_front(&mut self) -> Option<SpawnRequest> {
+        if self.requests.is_empty() {
+            None
+        } else {
+            Some(self.requests.remove(0))
+        }
+    }
+
+    /// Drops requests that have outlived their expiry.
+    pub fn prune(&mut self, time: u32) {
+        self.requests.retain(|r| !r.is_expired(time));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SpawnRequest> {
+        self.requests.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
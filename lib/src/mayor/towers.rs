@@ -0,0 +1,173 @@
+use super::{repairs::RAMPART_CRITICAL, Mayor};
+use crate::error::Res;
+use log::*;
+use screeps::{
+    constants::Part, find, Attackable, CanStoreEnergy, Creep as ScreepsCreep, HasId, HasPosition,
+    Structure, StructureProperties, StructureTower,
+};
+use std::collections::HashMap;
+
+const TOWER_POWER_ATTACK: u32 = 600;
+const TOWER_POWER_HEAL: u32 = 400;
+/// Towers work at full power up to this range, and at a quarter of it from
+/// `TOWER_FALLOFF_RANGE` on.
+const TOWER_OPTIMAL_RANGE: u32 = 5;
+const TOWER_FALLOFF_RANGE: u32 = 20;
+const TOWER_ENERGY_COST: u32 = 10;
+/// Energy towers keep for defence. Only what's above it goes into repairs.
+const TOWER_ENERGY_RESERVE: u32 = 500;
+
+/// Hits healed per HEAL part from next to the target, and from range 3.
+const HEAL_POWER: u32 = 12;
+const RANGED_HEAL_POWER: u32 = 4;
+
+impl<'a> Mayor<'a> {
+    /// Has the room's towers focus fire on the hostile they can hurt the
+    /// most, healers first. With nothing worth shooting they heal our
+    /// creeps, and in peace spend spare energy on repairs.
+    pub fn run_towers(&mut self) -> Res<()> {
+        let room = self.room.obj();
+        let towers = room
+            .find(find::MY_STRUCTURES)
+            .into_iter()
+            .filter_map(|s| match s {
+                Structure::Tower(tower) => Some(tower),
+                _ => None,
+            })
+            .filter(|t| t.energy() >= TOWER_ENERGY_COST)
+            .collect::<Vec<_>>();
+        if towers.is_empty() {
+            return Ok(());
+        }
+
+        let hostiles = room.find(find::HOSTILE_CREEPS);
+        if let Some(target) = focus_target(&towers, &hostiles) {
+            debug!("{}: towers shooting at {}", room.name(), target.name());
+            for tower in &towers {
+                tower.attack(target);
+            }
+            return Ok(());
+        }
+
+        let idle = heal_creeps(towers, room.find(find::MY_CREEPS));
+        if hostiles.is_empty() {
+            self.repair_with(idle);
+        }
+
+        Ok(())
+    }
+
+    /// Has towers with energy above their reserve repair the most urgent
+    /// structures that still need it.
+    fn repair_with(&self, towers: Vec<StructureTower>) {
+        let towers = towers
+            .into_iter()
+            .filter(|t| t.energy() > TOWER_ENERGY_RESERVE)
+            .collect::<Vec<_>>();
+        if towers.is_empty() {
+            return;
+        }
+
+        let structures = self
+            .room
+            .obj()
+            .find(find::STRUCTURES)
+            .into_iter()
+            .map(|s| (s.id(), s))
+            .collect::<HashMap<_, _>>();
+        // The queue is only refreshed now and then, so some may be done
+        let target = self
+            .tower_repairs()
+            .filter_map(|r| structures.get(&r.target_id).map(|s| (r, s)))
+            .find(|(repair, structure)| {
+                structure.as_attackable().map_or(false, |a| {
+                    let done = if repair.fortify {
+                        RAMPART_CRITICAL
+                    } else {
+                        a.hits_max()
+                    };
+                    a.hits() < done
+                })
+            });
+
+        if let Some((_, structure)) = target {
+            for tower in &towers {
+                tower.repair(structure);
+            }
+        }
+    }
+}
+
+/// What a tower's `power` comes down to at `range`.
+fn tower_power(power: u32, range: u32) -> u32 {
+    let range = range.max(TOWER_OPTIMAL_RANGE).min(TOWER_FALLOFF_RANGE);
+    let lost = power * 3 / 4 * (range - TOWER_OPTIMAL_RANGE);
+    power - lost / (TOWER_FALLOFF_RANGE - TOWER_OPTIMAL_RANGE)
+}
+
+/// The hostile to shoot: one with HEAL parts if any can be hurt, otherwise
+/// the one taking the most damage after its own and its friends' healing.
+/// Doesn't shoot at all if nothing can be hurt, so heal squads don't drain
+/// the towers. Boosts are ignored.
+fn focus_target<'h>(
+    towers: &[StructureTower],
+    hostiles: &'h [ScreepsCreep],
+) -> Option<&'h ScreepsCreep> {
+    hostiles
+        .iter()
+        .map(|h| (h, net_damage(towers, hostiles, h)))
+        .filter(|(_, damage)| *damage > 0)
+        .max_by_key(|(h, damage)| (h.get_active_bodyparts(Part::Heal) > 0, *damage))
+        .map(|(h, _)| h)
+}
+
+fn net_damage(towers: &[StructureTower], hostiles: &[ScreepsCreep], target: &ScreepsCreep) -> i64 {
+    let pos = target.pos();
+    let damage = towers
+        .iter()
+        .map(|t| tower_power(TOWER_POWER_ATTACK, t.pos().get_range_to(&pos)))
+        .sum::<u32>();
+    let healing = hostiles
+        .iter()
+        .map(|h| {
+            let parts = h.get_active_bodyparts(Part::Heal);
+            match h.pos().get_range_to(&pos) {
+                0..=1 => parts * HEAL_POWER,
+                2..=3 => parts * RANGED_HEAL_POWER,
+                _ => 0,
+            }
+        })
+        .sum::<u32>();
+    i64::from(damage) - i64::from(healing)
+}
+
+/// Has each tower heal whichever of `creeps` is missing the most hits after
+/// the towers before it. Returns the towers that had nobody to heal.
+fn heal_creeps(towers: Vec<StructureTower>, creeps: Vec<ScreepsCreep>) -> Vec<StructureTower> {
+    let mut wounded = creeps
+        .into_iter()
+        .filter(|c| !c.spawning() && c.hits() < c.hits_max())
+        .map(|c| {
+            let missing = c.hits_max() - c.hits();
+            (c, missing)
+        })
+        .collect::<Vec<_>>();
+
+    let mut idle = vec![];
+    for tower in towers {
+        let most = wounded
+            .iter_mut()
+            .filter(|(_, missing)| *missing > 0)
+            .max_by_key(|(_, missing)| *missing);
+        match most {
+            Some((creep, missing)) => {
+                tower.heal(creep);
+                let healed = tower_power(TOWER_POWER_HEAL, tower.pos().get_range_to(&creep.pos()));
+                *missing = missing.saturating_sub(healed);
+            }
+            None => idle.push(tower),
+        }
+    }
+
+    idle
+}
@@ -1,5 +1,9 @@
 use rand::{rngs::SmallRng, seq::IteratorRandom};
 use rand_core::SeedableRng;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
 
 pub const NAMES: [&str; 200] = [
     "Sugar",
@@ -204,12 +208,19 @@ pub const NAMES: [&str; 200] = [
     "Kit-Kat",
 ];
 
-pub fn get_random_name() -> String {
-    let mut rng = SmallRng::seed_from_u64(screeps::game::time().into());
+/// Picks an unused name, skipping `taken` as well as living creeps so that
+/// several spawns can be started in the same tick. Creeps spawned elsewhere
+/// this tick aren't known yet, so `seed` (the spawn's name) keeps different
+/// spawns from all picking the same one.
+pub fn get_random_name(seed: &str, taken: &[String]) -> String {
+    let mut hasher = DefaultHasher::new();
+    (seed, screeps::game::time()).hash(&mut hasher);
+    let mut rng = SmallRng::seed_from_u64(hasher.finish());
+    let creeps = screeps::game::creeps::hashmap();
     NAMES
         .iter()
-        .filter(|n| !screeps::game::creeps::hashmap().contains_key(**n))
+        .filter(|n| !creeps.contains_key(**n) && !taken.iter().any(|t| t == *n))
         .choose(&mut rng)
         .map(|s| s.to_string())
-        .unwrap_or_else(|| format!("Creep_{}", screeps::game::time()))
+        .unwrap_or_else(|| format!("Creep_{}_{}", screeps::game::time(), taken.len()))
 }
@@ -0,0 +1,629 @@
+use crate::terrain::{
+    all_tiles, flood_fill, neighbours, offset, range, square, Grid, Terrain, Tile, UNREACHABLE,
+};
+use screeps::StructureType;
+use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
+
+pub use ramparts::{fortify_target, plan_ramparts, PROTECT_RANGE};
+
+mod ramparts;
+
+/// Structures are kept off the two tiles next to the room edge.
+const BUILD_MIN: u8 = 2;
+const BUILD_MAX: u8 = 47;
+
+/// Roads aren't worth building before towers can keep them repaired.
+pub const ROAD_RCL: u8 = 3;
+
+/// How far the core stamp reaches from the anchor, including its ring road.
+const CORE_RADIUS: i32 = 3;
+
+/// The core, relative to the anchor (the storage). Every other tile within
+/// `CORE_RADIUS` is a road. A hub creep standing at (0, -1) reaches the
+/// storage, terminal, hub link and first spawn.
+const CORE: &[(i32, i32, StructureType, u8)] = &[
+    (0, 0, StructureType::Storage, 4),
+    (-1, 0, StructureType::Terminal, 6),
+    (1, 0, StructureType::Link, 5),
+    (0, -2, StructureType::Spawn, 1),
+    (-2, -2, StructureType::Spawn, 7),
+    (2, -2, StructureType::Spawn, 8),
+    (0, 1, StructureType::Tower, 3),
+    (-1, 1, StructureType::Tower, 5),
+    (1, 1, StructureType::Tower, 7),
+    (-2, 2, StructureType::Tower, 8),
+    (0, 2, StructureType::Tower, 8),
+    (2, 2, StructureType::Tower, 8),
+];
+
+/// Labs on a 4×4 block split by a diagonal road, relative to its top left
+/// corner. The first two are the input labs, in range 2 of all the others.
+const LABS: &[(i32, i32, u8)] = &[
+    (2, 1, 6),
+    (1, 2, 6),
+    (0, 1, 6),
+    (3, 2, 7),
+    (2, 3, 7),
+    (1, 0, 7),
+    (3, 1, 8),
+    (2, 0, 8),
+    (0, 2, 8),
+    (1, 3, 8),
+];
+const LAB_ROADS: &[(i32, i32)] = &[(0, 0), (1, 1), (2, 2), (3, 3)];
+
+/// Extensions available at each RCL.
+const EXTENSIONS: [usize; 9] = [0, 0, 5, 10, 20, 30, 40, 50, 60];
+
+/// Link RCLs for the controller and then sources, farthest source first. The
+/// hub link is part of the core.
+const CONTROLLER_LINK_RCL: u8 = 5;
+const SOURCE_LINK_RCLS: &[u8] = &[6, 7, 8, 8];
+
+const EXTRACTOR_RCL: u8 = 6;
+
+const RAMPART_RCL: u8 = 4;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PlannedStructure {
+    pub structure: StructureType,
+    pub x: u8,
+    pub y: u8,
+    /// The controller level it's built at.
+    pub rcl: u8,
+}
+
+js_serializable!(PlannedStructure);
+js_deserializable!(PlannedStructure);
+
+impl PlannedStructure {
+    pub fn tile(&self) -> Tile {
+        (self.x, self.y)
+    }
+}
+
+/// Where everything in a room goes, from RCL 1 to 8.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Layout {
+    /// The storage, which everything else is planned around.
+    pub anchor: Tile,
+    pub structures: Vec<PlannedStructure>,
+    /// Where the harvester of each source stands, in the order the sources
+    /// were given.
+    #[serde(default)]
+    pub source_spots: Vec<Tile>,
+    #[serde(default)]
+    pub upgrade_spot: Option<Tile>,
+    /// Whether ramparts have been planned, which older layouts are missing.
+    #[serde(default)]
+    pub fortified: bool,
+}
+
+js_serializable!(Layout);
+js_deserializable!(Layout);
+
+impl Layout {
+    /// Everything that can be built at `rcl`.
+    pub fn up_to(&self, rcl: u8) -> impl Iterator<Item = &PlannedStructure> {
+        self.structures.iter().filter(move |s| s.rcl <= rcl)
+    }
+
+    pub fn of_type(&self, structure: StructureType) -> impl Iterator<Item = &PlannedStructure> {
+        self.structures
+            .iter()
+            .filter(move |s| s.structure == structure)
+    }
+}
+
+/// What the planner needs to know about a room.
+pub struct PlanInput<'a> {
+    pub terrain: &'a Terrain,
+    pub controller: Tile,
+    pub sources: Vec<Tile>,
+    pub mineral: Option<Tile>,
+    /// An existing spawn, which the core is placed around if it fits. If it
+    /// doesn't, the spawn is kept where it is in place of the core's first.
+    pub spawn: Option<Tile>,
+}
+
+/// Plans a full layout, or returns `None` if the core doesn't fit anywhere.
+/// Labs or extensions that don't fit are left out.
+pub fn plan(input: &PlanInput) -> Option<Layout> {
+    let mut planner = Planner::new(input);
+    planner.reserve_around_targets();
+    let anchor = planner.choose_anchor()?;
+    planner.layout.anchor = anchor;
+    planner.place_core(anchor);
+    planner.place_controller();
+    planner.place_sources();
+    planner.place_mineral();
+    planner.place_labs();
+    planner.place_roads();
+    planner.place_extensions();
+
+    let mut layout = planner.layout;
+    add_ramparts(&mut layout, input.terrain);
+    Some(layout)
+}
+
+/// Plans ramparts around the layout, replacing any it had. Roads and the
+/// mine structures are left outside.
+pub fn add_ramparts(layout: &mut Layout, terrain: &Terrain) {
+    layout
+        .structures
+        .retain(|s| s.structure != StructureType::Rampart);
+    layout.fortified = true;
+
+    let mut protect = Grid::new(false);
+    let at_source =
+        |s: &PlannedStructure| layout.source_spots.iter().any(|t| range(*t, s.tile()) <= 1);
+    let protected = layout
+        .structures
+        .iter()
+        .filter(|s| match s.structure {
+            StructureType::Road | StructureType::Extractor => false,
+            StructureType::Link => !at_source(s),
+            _ => true,
+        })
+        .map(|s| s.tile())
+        .chain(layout.upgrade_spot);
+    for tile in protected {
+        for near in square(tile, PROTECT_RANGE).flatten() {
+            protect[near] = true;
+        }
+    }
+
+    let protect = all_tiles().filter(|t| protect[*t]).collect::<Vec<_>>();
+    for (x, y) in plan_ramparts(terrain, &protect) {
+        layout.structures.push(PlannedStructure {
+            structure: StructureType::Rampart,
+            x,
+            y,
+            rcl: RAMPART_RCL,
+        });
+    }
+}
+
+struct Planner<'a> {
+    input: &'a PlanInput<'a>,
+    grid: Grid<Option<StructureType>>,
+    /// Tiles kept free for creeps to stand on.
+    reserved: Grid<bool>,
+    /// Where the roads out of the core start.
+    ring: Vec<Tile>,
+    /// Tiles roads are planned to.
+    destinations: Vec<Tile>,
+    layout: Layout,
+}
+
+impl<'a> Planner<'a> {
+    fn new(input: &'a PlanInput<'a>) -> Self {
+        Self {
+            input,
+            grid: Grid::new(None),
+            reserved: Grid::new(false),
+            ring: vec![],
+            destinations: vec![],
+            layout: Layout::default(),
+        }
+    }
+
+    fn is_free(&self, tile: Tile) -> bool {
+        let (x, y) = tile;
+        (BUILD_MIN..=BUILD_MAX).contains(&x)
+            && (BUILD_MIN..=BUILD_MAX).contains(&y)
+            && !self.input.terrain.is_wall(tile)
+            && self.grid[tile].is_none()
+            && !self.reserved[tile]
+    }
+
+    fn is_passable(&self, tile: Tile) -> bool {
+        !self.input.terrain.is_wall(tile)
+            && self.grid[tile].map_or(true, |s| s == StructureType::Road)
+    }
+
+    fn place(&mut self, tile: Tile, structure: StructureType, rcl: u8) {
+        self.grid[tile] = Some(structure);
+        self.layout.structures.push(PlannedStructure {
+            structure,
+            x: tile.0,
+            y: tile.1,
+            rcl,
+        });
+    }
+
+    fn place_road(&mut self, tile: Tile, rcl: u8) {
+        if self.grid[tile].is_none() && !self.input.terrain.is_wall(tile) {
+            self.place(tile, StructureType::Road, rcl);
+        }
+    }
+
+    /// Distances from the anchor through what's been planned so far.
+    fn distances_from_anchor(&self) -> Grid<u32> {
+        flood_fill(&[self.layout.anchor], |t| self.is_passable(t))
+    }
+
+    /// Keeps the tiles around sources, the controller and the mineral free
+    /// for the creeps working them.
+    fn reserve_around_targets(&mut self) {
+        let input = self.input;
+        let targets = input
+            .sources
+            .iter()
+            .chain(input.mineral.iter())
+            .chain(std::iter::once(&input.controller));
+        for target in targets {
+            for tile in neighbours(*target) {
+                self.reserved[tile] = true;
+            }
+        }
+    }
+
+    /// Picks the spot for the storage: around the existing spawn if the core
+    /// fits there, otherwise the spot closest to the controller and sources
+    /// that keeps clear of the spawn.
+    fn choose_anchor(&self) -> Option<Tile> {
+        let fits = |anchor: Tile| {
+            square(anchor, CORE_RADIUS).all(|t| t.map_or(false, |t| self.is_free(t)))
+        };
+
+        if let Some(anchor) = self.input.spawn.and_then(|s| offset(s, 0, 2)) {
+            if fits(anchor) {
+                return Some(anchor);
+            }
+        }
+
+        let terrain = self.input.terrain;
+        let maps = std::iter::once(&self.input.controller)
+            .chain(self.input.sources.iter())
+            .map(|t| terrain.flood_fill(&[*t], |_| true))
+            .collect::<Vec<_>>();
+        // Only tiles far enough from walls can fit the core at all
+        let open = terrain.distance_transform();
+
+        let spawn = self.input.spawn;
+        all_tiles()
+            .filter(|t| i32::from(open[*t]) > CORE_RADIUS)
+            .filter(|t| spawn.map_or(true, |s| range(s, *t) > CORE_RADIUS as u32))
+            .filter(|t| fits(*t))
+            .map(|t| {
+                let score = maps
+                    .iter()
+                    .fold(0u32, |score, map| score.saturating_add(map[t]));
+                (t, score)
+            })
+            .filter(|(_, score)| *score < UNREACHABLE)
+            .min_by_key(|(_, score)| *score)
+            .map(|(t, _)| t)
+    }
+
+    fn place_core(&mut self, anchor: Tile) {
+        // An existing spawn outside the core takes the place of its first
+        let outside = self
+            .input
+            .spawn
+            .filter(|s| range(*s, anchor) > CORE_RADIUS as u32);
+        if let Some(spawn) = outside {
+            self.place(spawn, StructureType::Spawn, 1);
+        }
+        for &(dx, dy, structure, rcl) in CORE {
+            if outside.is_some() && structure == StructureType::Spawn && rcl == 1 {
+                continue;
+            }
+            if let Some(tile) = offset(anchor, dx, dy) {
+                self.place(tile, structure, rcl);
+            }
+        }
+        for tile in square(anchor, CORE_RADIUS).flatten() {
+            let (dx, dy) = delta(anchor, tile);
+            if dx.abs().max(dy.abs()) == CORE_RADIUS {
+                self.ring.push(tile);
+            }
+            self.place_road(tile, ROAD_RCL);
+        }
+        if let Some(hub) = offset(anchor, 0, -1) {
+            self.reserved[hub] = true;
+        }
+    }
+
+    /// The spot an upgrader stands on, with the controller link next to it.
+    fn place_controller(&mut self) {
+        let from_anchor = self.distances_from_anchor();
+        let controller = self.input.controller;
+        let spot = match self.standing_spot(controller, 3, &from_anchor) {
+            Some(spot) => spot,
+            None => return,
+        };
+        self.reserved[spot] = true;
+        self.layout.upgrade_spot = Some(spot);
+        self.destinations.push(spot);
+
+        if let Some(link) = self.closest_free(neighbours(spot), &from_anchor) {
+            self.place(link, StructureType::Link, CONTROLLER_LINK_RCL);
+        }
+    }
+
+    /// The spot each source's harvester stands on, with a link next to it.
+    /// The farthest sources get their links first.
+    fn place_sources(&mut self) {
+        let from_anchor = self.distances_from_anchor();
+        let spots = self
+            .input
+            .sources
+            .iter()
+            .map(|s| self.standing_spot(*s, 1, &from_anchor))
+            .collect::<Vec<_>>();
+
+        let mut by_distance = spots.iter().filter_map(|s| *s).collect::<Vec<_>>();
+        by_distance.sort_by_key(|s| std::cmp::Reverse(from_anchor[*s]));
+        for (spot, rcl) in by_distance.iter().zip(SOURCE_LINK_RCLS) {
+            self.reserved[*spot] = true;
+            self.destinations.push(*spot);
+            if let Some(link) = self.closest_free(neighbours(*spot), &from_anchor) {
+                self.place(link, StructureType::Link, *rcl);
+            }
+        }
+
+        // Unreachable sources keep their place so the indices line up
+        self.layout.source_spots = spots
+            .into_iter()
+            .map(|s| s.unwrap_or(self.layout.anchor))
+            .collect();
+    }
+
+    fn place_mineral(&mut self) {
+        let mineral = match self.input.mineral {
+            Some(mineral) => mineral,
+            None => return,
+        };
+        let from_anchor = self.distances_from_anchor();
+        self.place(mineral, StructureType::Extractor, EXTRACTOR_RCL);
+        if let Some(spot) = self.standing_spot(mineral, 1, &from_anchor) {
+            self.reserved[spot] = true;
+            self.destinations.push(spot);
+        }
+    }
+
+    /// Puts the lab block as close to the core as it fits.
+    fn place_labs(&mut self) {
+        let from_anchor = self.distances_from_anchor();
+        let fits = |corner: Tile| {
+            (0..4).all(|dy| {
+                (0..4).all(|dx| offset(corner, dx, dy).map_or(false, |t| self.is_free(t)))
+            })
+        };
+        let corner = all_tiles()
+            .filter(|t| from_anchor[*t] < UNREACHABLE)
+            .filter(|t| fits(*t))
+            .min_by_key(|t| from_anchor[*t]);
+        let corner = match corner {
+            Some(corner) => corner,
+            None => return,
+        };
+
+        for &(dx, dy, rcl) in LABS {
+            if let Some(tile) = offset(corner, dx, dy) {
+                self.place(tile, StructureType::Lab, rcl);
+            }
+        }
+        for &(dx, dy) in LAB_ROADS {
+            if let Some(tile) = offset(corner, dx, dy) {
+                self.place_road(tile, 6);
+            }
+        }
+        self.destinations.push(corner);
+    }
+
+    /// Roads from the core's ring to every spot creeps work at.
+    fn place_roads(&mut self) {
+        for destination in self.destinations.clone() {
+            let to_ring = flood_fill(&self.ring, |t| self.is_passable(t));
+            let mut tile = destination;
+            while to_ring[tile] > 0 && to_ring[tile] < UNREACHABLE {
+                let next = neighbours(tile).find(|n| to_ring[*n] == to_ring[tile] - 1);
+                tile = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                if !self.reserved[tile] {
+                    self.place_road(tile, ROAD_RCL);
+                }
+            }
+        }
+    }
+
+    /// Fills the closest free tiles of one checkerboard colour with
+    /// extensions, with roads on the other colour between them.
+    fn place_extensions(&mut self) {
+        let (ax, ay) = self.layout.anchor;
+        let from_anchor = self.distances_from_anchor();
+        let mut candidates = all_tiles()
+            .filter(|(x, y)| {
+                (u32::from(*x) + u32::from(*y) + u32::from(ax) + u32::from(ay)) % 2 == 1
+            })
+            .filter(|t| from_anchor[*t] < UNREACHABLE)
+            .filter(|t| self.is_free(*t))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|t| from_anchor[*t]);
+
+        let total = EXTENSIONS[EXTENSIONS.len() - 1];
+        for (i, tile) in candidates.into_iter().take(total).enumerate() {
+            let rcl = EXTENSIONS.iter().position(|n| *n > i).unwrap_or(8) as u8;
+            self.place(tile, StructureType::Extension, rcl);
+            for (dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                if let Some(road) = offset(tile, *dx, *dy) {
+                    if self.is_free(road) {
+                        self.place_road(road, rcl.max(ROAD_RCL));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The walkable tile within `range` of `target` closest to the anchor.
+    fn standing_spot(&self, target: Tile, range: i32, from_anchor: &Grid<u32>) -> Option<Tile> {
+        square(target, range)
+            .flatten()
+            .filter(|t| *t != target && self.grid[*t].is_none())
+            .filter(|t| !self.input.terrain.is_wall(*t))
+            .filter(|t| from_anchor[*t] < UNREACHABLE)
+            .min_by_key(|t| from_anchor[*t])
+    }
+
+    fn closest_free<I: Iterator<Item = Tile>>(
+        &self,
+        tiles: I,
+        from_anchor: &Grid<u32>,
+    ) -> Option<Tile> {
+        tiles
+            .filter(|t| self.is_free(*t))
+            .min_by_key(|t| from_anchor[*t])
+    }
+}
+
+fn delta((ax, ay): Tile, (bx, by): Tile) -> (i32, i32) {
+    (i32::from(bx) - i32::from(ax), i32::from(by) - i32::from(ay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StructureType::*;
+
+    const ROOM: &str = "
+    ############......################################
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #...........................####.................#
+    #...........................####.................#
+    #...........................####.................#
+    #...........................####.................#
+    #...........................####.................#
+    #...........................####.................#
+    #...........................####.................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #....#####........................................
+    #....#####........................................
+    #....#####........................................
+    #....#####........................................
+    #....#####........................................
+    #....#####........................................
+    #....#####........................................
+    #....#####.......................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #.#######################........................#
+    #................................................#
+    #................................................#
+    #.....................................#######....#
+    #.....................................#######....#
+    #.....................................#######....#
+    #.....................................#######....#
+    #.............~~~~~...................#######....#
+    #.............~~~~~...................#######....#
+    #.............~~~~~..............................#
+    #.............~~~~~..............................#
+    #.............~~~~~..............................#
+    #................................................#
+    #................................................#
+    #................................................#
+    #................................................#
+    ##############################.....###############
+    ";
+
+    fn input(terrain: &Terrain, spawn: Option<Tile>) -> PlanInput<'_> {
+        PlanInput {
+            terrain,
+            controller: (10, 10),
+            sources: vec![(27, 8), (40, 44)],
+            mineral: Some((45, 38)),
+            spawn,
+        }
+    }
+
+    fn count(layout: &Layout, structure: StructureType, rcl: u8) -> usize {
+        layout
+            .up_to(rcl)
+            .filter(|s| s.structure == structure)
+            .count()
+    }
+
+    #[test]
+    fn structure_counts_follow_rcl() {
+        let terrain = Terrain::parse(ROOM).unwrap();
+        let layout = plan(&input(&terrain, None)).unwrap();
+
+        let spawns = [0, 1, 1, 1, 1, 1, 1, 2, 3];
+        let towers = [0, 0, 0, 1, 1, 2, 2, 3, 6];
+        let labs = [0, 0, 0, 0, 0, 0, 3, 6, 10];
+        let links = [0, 0, 0, 0, 0, 2, 3, 4, 4];
+        for rcl in 1..=8 {
+            let i = rcl as usize;
+            assert_eq!(count(&layout, Spawn, rcl), spawns[i], "spawns at {}", rcl);
+            assert_eq!(
+                count(&layout, Extension, rcl),
+                EXTENSIONS[i],
+                "extensions at {}",
+                rcl
+            );
+            assert_eq!(count(&layout, Tower, rcl), towers[i], "towers at {}", rcl);
+            assert_eq!(count(&layout, Lab, rcl), labs[i], "labs at {}", rcl);
+            assert_eq!(count(&layout, Link, rcl), links[i], "links at {}", rcl);
+            assert_eq!(count(&layout, Storage, rcl), (rcl >= 4) as usize);
+            assert_eq!(count(&layout, Terminal, rcl), (rcl >= 6) as usize);
+            assert_eq!(count(&layout, Extractor, rcl), (rcl >= 6) as usize);
+        }
+    }
+
+    #[test]
+    fn nothing_on_walls_or_stacked() {
+        let terrain = Terrain::parse(ROOM).unwrap();
+        let layout = plan(&input(&terrain, None)).unwrap();
+
+        let mut taken = Grid::new(false);
+        for s in layout.structures.iter().filter(|s| s.structure != Rampart) {
+            assert!(!terrain.is_wall(s.tile()), "{:?} on a wall", s);
+            assert!(!taken[s.tile()], "{:?} on a taken tile", s);
+            taken[s.tile()] = true;
+        }
+    }
+
+    #[test]
+    fn core_goes_around_existing_spawn() {
+        let terrain = Terrain::parse(ROOM).unwrap();
+        let layout = plan(&input(&terrain, Some((24, 22)))).unwrap();
+
+        assert_eq!(layout.anchor, (24, 24));
+        assert_eq!(count(&layout, Spawn, 8), 3);
+    }
+
+    #[test]
+    fn keeps_existing_spawn_the_core_does_not_fit_around() {
+        let terrain = Terrain::parse(ROOM).unwrap();
+        let spawn = (2, 30);
+        let layout = plan(&input(&terrain, Some(spawn))).unwrap();
+
+        assert!(range(spawn, layout.anchor) > CORE_RADIUS as u32);
+        let first = layout
+            .of_type(Spawn)
+            .filter(|s| s.rcl == 1)
+            .collect::<Vec<_>>();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].tile(), spawn);
+        assert_eq!(count(&layout, Spawn, 8), 3);
+        let mut on_spawn = layout.structures.iter().filter(|s| s.tile() == spawn);
+        assert!(on_spawn.all(|s| s.structure == Spawn || s.structure == Rampart));
+    }
+}
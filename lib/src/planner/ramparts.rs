@@ -0,0 +1,260 @@
+use crate::terrain::{
+    all_tiles, index, is_edge, neighbours, square, Grid, Terrain, Tile, ROOM_SIZE,
+};
+use std::collections::VecDeque;
+
+/// Stands in for "can't be cut" in the flow graph.
+const INFINITE: u32 = 1_000_000;
+
+/// Hits to fortify walls and ramparts to at each controller level.
+const FORTIFY_TARGETS: [u32; 9] = [
+    0, 0, 10_000, 30_000, 100_000, 300_000, 1_000_000, 3_000_000, 10_000_000,
+];
+
+/// How far fortifications are kept from what they protect, so attackers
+/// can't reach over them.
+pub const PROTECT_RANGE: i32 = 2;
+
+pub fn fortify_target(rcl: u32) -> u32 {
+    FORTIFY_TARGETS[(rcl as usize).min(FORTIFY_TARGETS.len() - 1)]
+}
+
+/// The fewest tiles that, walled off, keep everything in `protect` from
+/// being reached from the room's exits.
+///
+/// Works as a minimum cut between the exits and `protect`, where cutting a
+/// tile costs one and tiles next to exits can't be cut. Protected tiles that
+/// touch those can't be walled off and are left out, so the cut never has to
+/// go through a tile nothing can be built on.
+pub fn plan_ramparts(terrain: &Terrain, protect: &[Tile]) -> Vec<Tile> {
+    let exits = exit_zone(terrain);
+    let near_exit = |t: Tile| square(t, 1).flatten().any(|n| exits[n]);
+    let protected = protect
+        .iter()
+        .filter(|t| !terrain.is_wall(**t) && !near_exit(**t))
+        .cloned()
+        .collect::<Vec<_>>();
+    if protected.is_empty() {
+        return vec![];
+    }
+
+    let source = 2 * ROOM_SIZE * ROOM_SIZE;
+    let sink = source + 1;
+    let mut graph = FlowGraph::new(sink + 1);
+    let is_protected = Grid::from_fn(|t| protected.contains(&t));
+
+    for tile in all_tiles().filter(|t| !terrain.is_wall(*t)) {
+        let (node_in, node_out) = nodes(tile);
+        let capacity = if is_protected[tile] || exits[tile] {
+            INFINITE
+        } else {
+            1
+        };
+        graph.add_edge(node_in, node_out, capacity);
+        for neighbour in neighbours(tile).filter(|n| !terrain.is_wall(*n)) {
+            graph.add_edge(node_out, nodes(neighbour).0, INFINITE);
+        }
+        if is_protected[tile] {
+            graph.add_edge(source, node_in, INFINITE);
+        }
+        if exits[tile] {
+            graph.add_edge(node_out, sink, INFINITE);
+        }
+    }
+
+    graph.max_flow(source, sink);
+    let reached = graph.reachable(source);
+    all_tiles()
+        .filter(|t| !terrain.is_wall(*t) && !exits[*t] && !is_protected[*t])
+        .filter(|t| {
+            let (node_in, node_out) = nodes(*t);
+            reached[node_in] && !reached[node_out]
+        })
+        .collect()
+}
+
+/// Exit tiles and the walkable tiles next to them, where nothing can be
+/// built to hold back attackers.
+fn exit_zone(terrain: &Terrain) -> Grid<bool> {
+    let mut zone = Grid::new(false);
+    for tile in all_tiles().filter(|t| is_edge(*t) && !terrain.is_wall(*t)) {
+        for near in square(tile, 1).flatten() {
+            if !terrain.is_wall(near) {
+                zone[near] = true;
+            }
+        }
+    }
+    zone
+}
+
+fn nodes(tile: Tile) -> (usize, usize) {
+    (2 * index(tile), 2 * index(tile) + 1)
+}
+
+/// A flow network for Dinic's algorithm.
+struct FlowGraph {
+    /// Target and remaining capacity, with each edge's reverse right after
+    /// it.
+    edges: Vec<(usize, u32)>,
+    adjacency: Vec<Vec<usize>>,
+    level: Vec<i32>,
+    next: Vec<usize>,
+}
+
+impl FlowGraph {
+    fn new(nodes: usize) -> Self {
+        Self {
+            edges: vec![],
+            adjacency: vec![vec![]; nodes],
+            level: vec![-1; nodes],
+            next: vec![0; nodes],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: u32) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push((to, capacity));
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push((from, 0));
+    }
+
+    fn max_flow(&mut self, source: usize, sink: usize) -> u32 {
+        let mut flow = 0;
+        while self.build_levels(source, sink) {
+            for next in self.next.iter_mut() {
+                *next = 0;
+            }
+            loop {
+                let pushed = self.push(source, sink, INFINITE);
+                if pushed == 0 {
+                    break;
+                }
+                flow += pushed;
+            }
+        }
+        flow
+    }
+
+    fn build_levels(&mut self, source: usize, sink: usize) -> bool {
+        for level in self.level.iter_mut() {
+            *level = -1;
+        }
+        self.level[source] = 0;
+        let mut queue = VecDeque::new();
+        queue.push_back(source);
+        while let Some(node) = queue.pop_front() {
+            for &edge in &self.adjacency[node] {
+                let (to, capacity) = self.edges[edge];
+                if capacity > 0 && self.level[to] < 0 {
+                    self.level[to] = self.level[node] + 1;
+                    queue.push_back(to);
+                }
+            }
+        }
+        self.level[sink] >= 0
+    }
+
+    fn push(&mut self, node: usize, sink: usize, limit: u32) -> u32 {
+        if node == sink {
+            return limit;
+        }
+        while self.next[node] < self.adjacency[node].len() {
+            let edge = self.adjacency[node][self.next[node]];
+            let (to, capacity) = self.edges[edge];
+            if capacity > 0 && self.level[to] == self.level[node] + 1 {
+                let pushed = self.push(to, sink, limit.min(capacity));
+                if pushed > 0 {
+                    self.edges[edge].1 -= pushed;
+                    self.edges[edge ^ 1].1 += pushed;
+                    return pushed;
+                }
+            }
+            self.next[node] += 1;
+        }
+        0
+    }
+
+    /// Nodes still reachable from `source` through edges with capacity left.
+    fn reachable(&self, source: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.adjacency.len()];
+        reached[source] = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(source);
+        while let Some(node) = queue.pop_front() {
+            for &edge in &self.adjacency[node] {
+                let (to, capacity) = self.edges[edge];
+                if capacity > 0 && !reached[to] {
+                    reached[to] = true;
+                    queue.push_back(to);
+                }
+            }
+        }
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::{flood_fill, Cell};
+
+    /// Walls around the room but for an exit along the top, from x = 20 to
+    /// 24, and a wall across y = 10 with a gap from x = 20 to 22.
+    fn room() -> Terrain {
+        Terrain::new(|(x, y)| {
+            let edge = is_edge((x, y)) && !(y == 0 && (20..=24).contains(&x));
+            let wall = y == 10 && !(20..=22).contains(&x);
+            if edge || wall {
+                Cell::Wall
+            } else {
+                Cell::Plain
+            }
+        })
+    }
+
+    /// Whether walking in from the exits reaches any of `protect` past
+    /// `ramparts`.
+    fn breached(terrain: &Terrain, protect: &[Tile], ramparts: &[Tile]) -> bool {
+        let exits = all_tiles()
+            .filter(|t| is_edge(*t) && !terrain.is_wall(*t))
+            .collect::<Vec<_>>();
+        let reached = flood_fill(&exits, |t| !terrain.is_wall(t) && !ramparts.contains(&t));
+        protect
+            .iter()
+            .any(|t| reached[*t] != crate::terrain::UNREACHABLE)
+    }
+
+    #[test]
+    fn walls_off_the_narrowest_gap() {
+        let terrain = room();
+        let protect = [(25, 30), (26, 30)];
+        let ramparts = plan_ramparts(&terrain, &protect);
+
+        assert_eq!(ramparts, vec![(20, 10), (21, 10), (22, 10)]);
+        assert!(!breached(&terrain, &protect, &ramparts));
+    }
+
+    #[test]
+    fn leaves_out_what_touches_the_exits() {
+        let terrain = room();
+        // Reaches right up to the tiles next to the exit
+        let protect = all_tiles()
+            .filter(|(x, y)| (18..=26).contains(x) && (2..=8).contains(y))
+            .collect::<Vec<_>>();
+        let ramparts = plan_ramparts(&terrain, &protect);
+
+        let exits = exit_zone(&terrain);
+        let kept = protect
+            .iter()
+            .filter(|t| square(**t, 1).flatten().all(|n| !exits[n]))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(!ramparts.is_empty());
+        for tile in &ramparts {
+            assert!(!terrain.is_wall(*tile), "{:?} is a wall", tile);
+            assert!(!exits[*tile], "{:?} is next to an exit", tile);
+            assert!(!kept.contains(tile), "{:?} is protected", tile);
+        }
+        assert!(!breached(&terrain, &kept, &ramparts));
+    }
+}
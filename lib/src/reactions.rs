@@ -0,0 +1,142 @@
+use screeps::ResourceType::{self, *};
+
+/// What one `runReaction` makes, and uses of each input.
+pub const LAB_REACTION_AMOUNT: u32 = 5;
+
+/// Every compound labs can make, and the two it's made from.
+const REACTIONS: &[(ResourceType, ResourceType, ResourceType)] = &[
+    (Hydroxide, Hydrogen, Oxygen),
+    (ZynthiumKeanite, Zynthium, Keanium),
+    (UtriumLemergite, Utrium, Lemergium),
+    (Ghodium, ZynthiumKeanite, UtriumLemergite),
+    (UtriumHydride, Utrium, Hydrogen),
+    (UtriumOxide, Utrium, Oxygen),
+    (KeaniumHydride, Keanium, Hydrogen),
+    (KeaniumOxide, Keanium, Oxygen),
+    (LemergiumHydride, Lemergium, Hydrogen),
+    (LemergiumOxide, Lemergium, Oxygen),
+    (ZynthiumHydride, Zynthium, Hydrogen),
+    (ZynthiumOxide, Zynthium, Oxygen),
+    (GhodiumHydride, Ghodium, Hydrogen),
+    (GhodiumOxide, Ghodium, Oxygen),
+    (UtriumAcid, UtriumHydride, Hydroxide),
+    (UtriumAlkalide, UtriumOxide, Hydroxide),
+    (KeaniumAcid, KeaniumHydride, Hydroxide),
+    (KeaniumAlkalide, KeaniumOxide, Hydroxide),
+    (LemergiumAcid, LemergiumHydride, Hydroxide),
+    (LemergiumAlkalide, LemergiumOxide, Hydroxide),
+    (ZynthiumAcid, ZynthiumHydride, Hydroxide),
+    (ZynthiumAlkalide, ZynthiumOxide, Hydroxide),
+    (GhodiumAcid, GhodiumHydride, Hydroxide),
+    (GhodiumAlkalide, GhodiumOxide, Hydroxide),
+    (CatalyzedUtriumAcid, UtriumAcid, Catalyst),
+    (CatalyzedUtriumAlkalide, UtriumAlkalide, Catalyst),
+    (CatalyzedKeaniumAcid, KeaniumAcid, Catalyst),
+    (CatalyzedKeaniumAlkalide, KeaniumAlkalide, Catalyst),
+    (CatalyzedLemergiumAcid, LemergiumAcid, Catalyst),
+    (CatalyzedLemergiumAlkalide, LemergiumAlkalide, Catalyst),
+    (CatalyzedZynthiumAcid, ZynthiumAcid, Catalyst),
+    (CatalyzedZynthiumAlkalide, ZynthiumAlkalide, Catalyst),
+    (CatalyzedGhodiumAcid, GhodiumAcid, Catalyst),
+    (CatalyzedGhodiumAlkalide, GhodiumAlkalide, Catalyst),
+];
+
+/// The two resources `product` is made from, or `None` for base minerals.
+pub fn components(product: ResourceType) -> Option<[ResourceType; 2]> {
+    REACTIONS
+        .iter()
+        .find(|(p, _, _)| *p == product)
+        .map(|(_, a, b)| [*a, *b])
+}
+
+/// The reaction to run next towards having `amount` of `target`, and how
+/// much of its product to make: `target` itself if both its inputs are in
+/// `stock`, else whatever's missing furthest down the tree. With an input
+/// short that can't be made, as much as the stock allows. `None` if there's
+/// enough already or nothing can be made.
+pub fn next_reaction<F>(target: ResourceType, amount: u32, stock: &F) -> Option<(ResourceType, u32)>
+where
+    F: Fn(ResourceType) -> u32,
+{
+    let needed = amount.saturating_sub(stock(target));
+    let inputs = components(target)?;
+    if needed < LAB_REACTION_AMOUNT {
+        return None;
+    }
+
+    for input in &inputs {
+        if stock(*input) < needed {
+            if let Some(reaction) = next_reaction(*input, needed, stock) {
+                return Some(reaction);
+            }
+        }
+    }
+
+    let batch = inputs.iter().map(|i| stock(*i)).fold(needed, u32::min);
+    let batch = batch - batch % LAB_REACTION_AMOUNT;
+    if batch == 0 {
+        None
+    } else {
+        Some((target, batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn stock_of(stock: &[(ResourceType, u32)]) -> impl Fn(ResourceType) -> u32 {
+        let stock = stock.iter().cloned().collect::<HashMap<_, _>>();
+        move |resource| stock.get(&resource).cloned().unwrap_or(0)
+    }
+
+    #[test]
+    fn makes_as_much_as_a_short_base_mineral_allows() {
+        let stock = stock_of(&[(Utrium, 2000), (Hydrogen, 302)]);
+        assert_eq!(
+            next_reaction(UtriumHydride, 1000, &stock),
+            Some((UtriumHydride, 300))
+        );
+        assert_eq!(next_reaction(UtriumHydride, 1000, &stock_of(&[])), None);
+    }
+
+    #[test]
+    fn works_down_the_tree_to_what_is_missing() {
+        let base = [
+            Zynthium, Keanium, Utrium, Lemergium, Oxygen, Hydrogen, Catalyst,
+        ];
+        let mut stock = base.iter().map(|r| (*r, 1000)).collect::<Vec<_>>();
+        let target = CatalyzedGhodiumAlkalide;
+        assert_eq!(
+            next_reaction(target, 100, &stock_of(&stock)),
+            Some((ZynthiumKeanite, 100))
+        );
+
+        stock.extend(&[(ZynthiumKeanite, 100), (UtriumLemergite, 100)]);
+        assert_eq!(
+            next_reaction(target, 100, &stock_of(&stock)),
+            Some((Ghodium, 100))
+        );
+
+        stock.extend(&[(Ghodium, 100), (GhodiumOxide, 100)]);
+        assert_eq!(
+            next_reaction(target, 100, &stock_of(&stock)),
+            Some((Hydroxide, 100))
+        );
+
+        stock.extend(&[(Hydroxide, 100), (GhodiumAlkalide, 100)]);
+        assert_eq!(
+            next_reaction(target, 100, &stock_of(&stock)),
+            Some((target, 100))
+        );
+    }
+
+    #[test]
+    fn nothing_to_make_with_enough_in_stock() {
+        let stock = stock_of(&[(CatalyzedGhodiumAlkalide, 3000)]);
+        assert_eq!(next_reaction(CatalyzedGhodiumAlkalide, 3000, &stock), None);
+        assert_eq!(next_reaction(CatalyzedGhodiumAlkalide, 3004, &stock), None);
+        assert_eq!(next_reaction(Hydrogen, 3000, &stock), None);
+    }
+}
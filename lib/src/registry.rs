@@ -0,0 +1,93 @@
+use crate::{controllers::FaultController, creeps::Creep, rooms::Room};
+use screeps::RoomName;
+use std::collections::HashMap;
+
+/// Every creep and owned room with its memory, loaded once at the start of
+/// the tick and lent out to whoever needs it. Changed memory is written back
+/// by `save` at the end of the tick.
+pub struct Registry {
+    creeps: HashMap<String, Creep>,
+    rooms: HashMap<RoomName, Room>,
+}
+
+impl Registry {
+    /// Loads everything, leaving out entities whose memory can't be read.
+    pub fn load(faults: &mut FaultController) -> Self {
+        let creeps = screeps::game::creeps::values()
+            .into_iter()
+            .filter_map(|c| {
+                let name = c.name();
+                faults
+                    .guard(&creep_entity(&name), || Creep::new(c))
+                    .map(|creep| (name, creep))
+            })
+            .collect();
+        let rooms = screeps::game::rooms::values()
+            .into_iter()
+            .filter(|r| r.controller().map_or(false, |c| c.my()))
+            .filter_map(|r| {
+                let name = r.name();
+                faults
+                    .guard(&room_entity(name), || Room::new(r))
+                    .map(|room| (name, room))
+            })
+            .collect();
+
+        Self { creeps, rooms }
+    }
+
+    pub fn creep(&self, name: &str) -> Option<&Creep> {
+        self.creeps.get(name)
+    }
+
+    pub fn creep_mut(&mut self, name: &str) -> Option<&mut Creep> {
+        self.creeps.get_mut(name)
+    }
+
+    pub fn creeps(&self) -> impl Iterator<Item = &Creep> {
+        self.creeps.values()
+    }
+
+    pub fn creeps_mut(&mut self) -> impl Iterator<Item = &mut Creep> {
+        self.creeps.values_mut()
+    }
+
+    pub fn room(&self, name: RoomName) -> Option<&Room> {
+        self.rooms.get(&name)
+    }
+
+    pub fn room_mut(&mut self, name: RoomName) -> Option<&mut Room> {
+        self.rooms.get_mut(&name)
+    }
+
+    /// An owned room together with the creeps whose home it is.
+    pub fn room_and_creeps(&mut self, name: RoomName) -> Option<(&mut Room, Vec<&mut Creep>)> {
+        let room = self.rooms.get_mut(&name)?;
+        let creeps = self
+            .creeps
+            .values_mut()
+            .filter(|c| c.memory().home == name)
+            .collect();
+        Some((room, creeps))
+    }
+
+    /// Writes back the memory of everything that changed this tick.
+    pub fn save(self) {
+        for (_, creep) in self.creeps {
+            creep.save();
+        }
+        for (_, room) in self.rooms {
+            room.save();
+        }
+    }
+}
+
+/// How a creep is named in fault tracking.
+pub fn creep_entity(name: &str) -> String {
+    format!("creep:{}", name)
+}
+
+/// How a room is named in fault tracking.
+pub fn room_entity(name: RoomName) -> String {
+    format!("room:{}", name)
+}
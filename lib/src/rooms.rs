@@ -1,4 +1,10 @@
-use crate::{error::Res, Id};
+use crate::{
+    config::Config,
+    error::{ErrorKind, Res},
+    mayor::{Mine, Reaction, RepairTask, Reservations, SpawnQueue, Traffic},
+    planner::Layout,
+    Id,
+};
 use screeps::{Position, ResourceType, Room as ScreepsRoom};
 use std::collections::HashMap;
 use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
@@ -6,43 +12,152 @@ use stdweb::{__js_serializable_boilerplate, js_deserializable, js_serializable};
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct RoomMemory {
     #[serde(default)]
-    pub mines: HashMap<ResourceType, Vec<Position>>,
+    pub mines: HashMap<ResourceType, Vec<Mine>>,
     #[serde(default)]
     pub extension_containers: Vec<Id>,
     #[serde(default)]
     pub extension_spots: Vec<Position>,
     #[serde(default)]
     pub lab_spots: HashMap<ResourceType, Id>,
+    /// The labs in `lab_spots` the Mayor set aside itself, and frees again.
+    #[serde(default)]
+    pub boost_labs: Vec<Id>,
     #[serde(default)]
     pub forts: Vec<Position>,
     #[serde(default)]
     pub repair_blacklist: Vec<Id>,
+    /// Damaged structures, most urgent first.
+    #[serde(default)]
+    pub repairs: Vec<RepairTask>,
+    #[serde(default)]
+    pub spawn_queue: SpawnQueue,
+    #[serde(default)]
+    pub recovering: bool,
+    #[serde(default)]
+    pub reservations: Reservations,
+    /// The planned base, filled in once by the Mayor.
+    #[serde(default)]
+    pub layout: Option<Layout>,
+    #[serde(default)]
+    pub layout_failed: bool,
+    /// The controller level construction sites were last placed for.
+    #[serde(default)]
+    pub built_rcl: u8,
+    /// Roads planned on top of the layout's own.
+    #[serde(default)]
+    pub roads: Vec<Position>,
+    #[serde(default)]
+    pub roads_planned: u32,
+    #[serde(default)]
+    pub traffic: Traffic,
+    /// What the labs are making, if anything.
+    #[serde(default)]
+    pub reaction: Option<Reaction>,
+    /// Equips too big for the room, with the energy capacity they were
+    /// rejected at, so they're only reported again once that changes.
+    #[serde(default)]
+    pub unspawnable: HashMap<String, u32>,
 }
 
 js_serializable!(RoomMemory);
 js_deserializable!(RoomMemory);
 
-pub struct Room {
-    obj: ScreepsRoom,
-    memory: Option<RoomMemory>,
+/// How a room relates to us, which decides what manages it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoomKind {
+    #[serde(rename = "O")]
+    Owned,
+    #[serde(rename = "R")]
+    Reserved,
+    #[serde(rename = "M")]
+    Remote,
+    #[serde(rename = "X")]
+    Hostile,
+    #[serde(rename = "H")]
+    Highway,
+    #[serde(rename = "N")]
+    Neutral,
 }
 
-impl Drop for Room {
-    fn drop(&mut self) {
-        screeps::memory::root().path_set(&format!("rooms.{}", self.obj.name()), self.memory.take());
+js_serializable!(RoomKind);
+js_deserializable!(RoomKind);
+
+impl RoomKind {
+    pub fn of(room: &ScreepsRoom, config: &Config) -> Self {
+        let name = room.name().to_string();
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None if is_highway(&name) => return RoomKind::Highway,
+            None => return RoomKind::Neutral,
+        };
+
+        if controller.my() {
+            return RoomKind::Owned;
+        }
+        if controller.owner_name().is_some() {
+            return RoomKind::Hostile;
+        }
+        if let Some(reservation) = controller.reservation() {
+            return if Some(reservation.username) == my_username() {
+                RoomKind::Reserved
+            } else {
+                RoomKind::Hostile
+            };
+        }
+        if config.memory().remote_rooms.contains(&name) {
+            RoomKind::Remote
+        } else {
+            RoomKind::Neutral
+        }
     }
 }
 
+/// Our username, taken from any of our spawns.
+fn my_username() -> Option<String> {
+    screeps::game::spawns::values()
+        .first()
+        .map(|s| s.owner_name())
+}
+
+/// Highway rooms are the ones with an x or y coordinate divisible by 10,
+/// like `W10N3` or `E4S20`.
+fn is_highway(name: &str) -> bool {
+    name.split(|c: char| c.is_ascii_alphabetic())
+        .filter(|n| !n.is_empty())
+        .any(|n| n.parse::<u32>().map_or(false, |n| n % 10 == 0))
+}
+
+pub struct Room {
+    obj: ScreepsRoom,
+    memory: Option<RoomMemory>,
+    dirty: bool,
+}
+
 impl Room {
     pub fn new(obj: ScreepsRoom) -> Res<Self> {
         let memory = Some(
             screeps::memory::root()
                 .dict_or_create("rooms")
-                .map_err(|_| "UnexpectedTypeError")?
+                .map_err(|_| ErrorKind::WrongObjectType {
+                    id: "Memory.rooms".to_string(),
+                    expected: "an object",
+                })?
                 .get(&obj.name().to_array_string())?
-                .ok_or_else(|| format!("undefined or null room memory for {}", obj.name()))?,
+                .unwrap_or_default(),
         );
-        Ok(Self { obj, memory })
+        Ok(Self {
+            obj,
+            memory,
+            dirty: false,
+        })
+    }
+
+    /// Writes the memory back if it was changed.
+    pub fn save(mut self) {
+        if self.dirty {
+            screeps::memory::root()
+                .path_set(&format!("rooms.{}", self.obj.name()), self.memory.take());
+        }
     }
 
     pub fn obj(&self) -> &ScreepsRoom {
@@ -54,6 +169,7 @@ impl Room {
     }
 
     pub fn memory_mut(&mut self) -> &mut RoomMemory {
+        self.dirty = true;
         self.memory.as_mut().expect("room.memory is not populated")
     }
 }
@@ -0,0 +1,489 @@
+use std::{
+    collections::VecDeque,
+    ops::{Index, IndexMut},
+};
+
+pub const ROOM_SIZE: usize = 50;
+
+/// Distance of tiles a flood fill didn't reach.
+pub const UNREACHABLE: u32 = u32::max_value();
+
+/// How far around a narrow passage is searched for a way past it.
+const CHOKEPOINT_SEARCH: i32 = 8;
+
+/// A tile in a room, as `(x, y)`.
+pub type Tile = (u8, u8);
+
+/// A value for every tile in a room.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            cells: vec![value; ROOM_SIZE * ROOM_SIZE],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn<F: FnMut(Tile) -> T>(f: F) -> Self {
+        Self {
+            cells: all_tiles().map(f).collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Tile, &T)> {
+        all_tiles().zip(self.cells.iter())
+    }
+}
+
+impl<T> Index<Tile> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, tile: Tile) -> &T {
+        &self.cells[index(tile)]
+    }
+}
+
+impl<T> IndexMut<Tile> for Grid<T> {
+    fn index_mut(&mut self, tile: Tile) -> &mut T {
+        &mut self.cells[index(tile)]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Plain,
+    Swamp,
+    Wall,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// A run of walkable tiles along one edge of the room.
+#[derive(Clone, Debug)]
+pub struct Exit {
+    pub side: Side,
+    pub tiles: Vec<Tile>,
+}
+
+/// A room's terrain, without anything built on it. Doesn't touch the game
+/// API, so everything here works natively as well.
+#[derive(Clone, Debug)]
+pub struct Terrain {
+    cells: Grid<Cell>,
+}
+
+impl Terrain {
+    pub fn new<F: FnMut(Tile) -> Cell>(f: F) -> Self {
+        Self {
+            cells: Grid::from_fn(f),
+        }
+    }
+
+    /// Reads a fixture of 50 lines of 50 characters: `#` for walls, `~` for
+    /// swamps and anything else for plains.
+    pub fn parse(fixture: &str) -> Option<Self> {
+        let rows = fixture
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>();
+        if rows.len() != ROOM_SIZE || rows.iter().any(|r| r.len() != ROOM_SIZE) {
+            return None;
+        }
+
+        Some(Self::new(|(x, y)| {
+            match rows[y as usize].as_bytes()[x as usize] {
+                b'#' => Cell::Wall,
+                b'~' => Cell::Swamp,
+                _ => Cell::Plain,
+            }
+        }))
+    }
+
+    pub fn get(&self, tile: Tile) -> Cell {
+        self.cells[tile]
+    }
+
+    pub fn is_wall(&self, tile: Tile) -> bool {
+        self.get(tile) == Cell::Wall
+    }
+
+    /// How many tiles each tile is from the nearest wall, counting diagonal
+    /// steps as one. Walls are 0, and so is everything outside the room.
+    pub fn distance_transform(&self) -> Grid<u8> {
+        let mut distance = Grid::from_fn(|t| if self.is_wall(t) { 0 } else { u8::max_value() });
+        let at =
+            |distance: &Grid<u8>, x: i32, y: i32| offset((0, 0), x, y).map_or(0, |t| distance[t]);
+
+        for y in 0..ROOM_SIZE as i32 {
+            for x in 0..ROOM_SIZE as i32 {
+                let tile = (x as u8, y as u8);
+                let nearest = [(-1, -1), (0, -1), (1, -1), (-1, 0)]
+                    .iter()
+                    .map(|(dx, dy)| at(&distance, x + dx, y + dy))
+                    .min()
+                    .unwrap_or(0);
+                distance[tile] = distance[tile].min(nearest.saturating_add(1));
+            }
+        }
+        for y in (0..ROOM_SIZE as i32).rev() {
+            for x in (0..ROOM_SIZE as i32).rev() {
+                let tile = (x as u8, y as u8);
+                let nearest = [(1, 1), (0, 1), (-1, 1), (1, 0)]
+                    .iter()
+                    .map(|(dx, dy)| at(&distance, x + dx, y + dy))
+                    .min()
+                    .unwrap_or(0);
+                distance[tile] = distance[tile].min(nearest.saturating_add(1));
+            }
+        }
+
+        distance
+    }
+
+    /// Steps from the nearest seed to every walkable tile, going only
+    /// through tiles that are also `passable`. Tiles it doesn't reach are
+    /// `UNREACHABLE`.
+    pub fn flood_fill<F: Fn(Tile) -> bool>(&self, seeds: &[Tile], passable: F) -> Grid<u32> {
+        flood_fill(seeds, |t| !self.is_wall(t) && passable(t))
+    }
+
+    pub fn exits(&self) -> Vec<Exit> {
+        let last = (ROOM_SIZE - 1) as u8;
+        let sides = [
+            (
+                Side::Top,
+                (0..ROOM_SIZE as u8).map(|x| (x, 0)).collect::<Vec<_>>(),
+            ),
+            (
+                Side::Right,
+                (0..ROOM_SIZE as u8).map(|y| (last, y)).collect(),
+            ),
+            (
+                Side::Bottom,
+                (0..ROOM_SIZE as u8).map(|x| (x, last)).collect(),
+            ),
+            (Side::Left, (0..ROOM_SIZE as u8).map(|y| (0, y)).collect()),
+        ];
+
+        let mut exits = vec![];
+        for (side, edge) in sides.iter() {
+            let mut run: Vec<Tile> = vec![];
+            for tile in edge {
+                if self.is_wall(*tile) {
+                    if !run.is_empty() {
+                        exits.push(Exit {
+                            side: *side,
+                            tiles: std::mem::take(&mut run),
+                        });
+                    }
+                } else {
+                    run.push(*tile);
+                }
+            }
+            if !run.is_empty() {
+                exits.push(Exit {
+                    side: *side,
+                    tiles: run,
+                });
+            }
+        }
+
+        exits
+    }
+
+    /// Gaps of at most `max_width` tiles between two walls that can't be
+    /// walked around nearby, narrowest first. Each gap is a straight
+    /// horizontal or vertical run of tiles.
+    pub fn chokepoints(&self, max_width: usize) -> Vec<Vec<Tile>> {
+        let mut chokepoints = vec![];
+        for tile in all_tiles().filter(|t| !self.is_wall(*t) && !is_edge(*t)) {
+            for &(dx, dy) in &[(1, 0), (0, 1)] {
+                // Only look at each gap from its first tile
+                if offset(tile, -dx, -dy).map_or(true, |t| !self.is_wall(t)) {
+                    continue;
+                }
+                let gap = match self.gap(tile, dx, dy, max_width) {
+                    Some(gap) => gap,
+                    None => continue,
+                };
+                let side = |sign: i32| {
+                    gap.iter()
+                        .filter_map(|t| offset(*t, sign * dy, sign * dx))
+                        .find(|t| !self.is_wall(*t))
+                };
+                if let (Some(a), Some(b)) = (side(1), side(-1)) {
+                    if !self.connected_around(a, b, &gap) {
+                        chokepoints.push(gap);
+                    }
+                }
+            }
+        }
+
+        chokepoints.sort_by_key(|gap| gap.len());
+        chokepoints
+    }
+
+    /// The walkable tiles from `start` in direction `(dx, dy)` up to the
+    /// next wall, if that's no more than `max_width` away and not past the
+    /// edge of the room.
+    fn gap(&self, start: Tile, dx: i32, dy: i32, max_width: usize) -> Option<Vec<Tile>> {
+        let mut gap = vec![];
+        let mut tile = start;
+        while !self.is_wall(tile) {
+            if is_edge(tile) || gap.len() == max_width {
+                return None;
+            }
+            gap.push(tile);
+            tile = offset(tile, dx, dy)?;
+        }
+        Some(gap)
+    }
+
+    /// Whether `a` can reach `b` without crossing `blocked`, staying close
+    /// to where `blocked` is.
+    fn connected_around(&self, a: Tile, b: Tile, blocked: &[Tile]) -> bool {
+        let centre = blocked[0];
+        let near = |t: Tile| {
+            let (dx, dy) = (
+                i32::from(t.0) - i32::from(centre.0),
+                i32::from(t.1) - i32::from(centre.1),
+            );
+            dx.abs().max(dy.abs()) <= CHOKEPOINT_SEARCH + blocked.len() as i32
+        };
+        let reached = self.flood_fill(&[a], |t| near(t) && !blocked.contains(&t));
+        reached[b] != UNREACHABLE
+    }
+}
+
+pub fn index((x, y): Tile) -> usize {
+    y as usize * ROOM_SIZE + x as usize
+}
+
+pub fn all_tiles() -> impl Iterator<Item = Tile> {
+    (0..ROOM_SIZE * ROOM_SIZE).map(|i| ((i % ROOM_SIZE) as u8, (i / ROOM_SIZE) as u8))
+}
+
+pub fn is_edge((x, y): Tile) -> bool {
+    let last = (ROOM_SIZE - 1) as u8;
+    x == 0 || y == 0 || x == last || y == last
+}
+
+pub fn offset((x, y): Tile, dx: i32, dy: i32) -> Option<Tile> {
+    let (x, y) = (i32::from(x) + dx, i32::from(y) + dy);
+    let size = ROOM_SIZE as i32;
+    if x >= 0 && x < size && y >= 0 && y < size {
+        Some((x as u8, y as u8))
+    } else {
+        None
+    }
+}
+
+/// Steps between two tiles, counting diagonal steps as one.
+pub fn range((ax, ay): Tile, (bx, by): Tile) -> u32 {
+    let dx = (i32::from(ax) - i32::from(bx)).abs();
+    let dy = (i32::from(ay) - i32::from(by)).abs();
+    dx.max(dy) as u32
+}
+
+/// The tiles within `radius` of `center`, `None` where it's outside the room.
+pub fn square(center: Tile, radius: i32) -> impl Iterator<Item = Option<Tile>> {
+    (-radius..=radius)
+        .flat_map(move |dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(move |(dx, dy)| offset(center, dx, dy))
+}
+
+pub fn neighbours(tile: Tile) -> impl Iterator<Item = Tile> {
+    square(tile, 1).flatten().filter(move |t| *t != tile)
+}
+
+/// Steps from the nearest seed to every tile, moving through tiles that are
+/// `passable`. Seeds count as reached even if they aren't passable.
+pub fn flood_fill<F: Fn(Tile) -> bool>(seeds: &[Tile], passable: F) -> Grid<u32> {
+    let mut distance = Grid::new(UNREACHABLE);
+    let mut queue = VecDeque::new();
+    for seed in seeds {
+        distance[*seed] = 0;
+        queue.push_back(*seed);
+    }
+
+    while let Some(tile) = queue.pop_front() {
+        let next = distance[tile] + 1;
+        for neighbour in neighbours(tile) {
+            if distance[neighbour] == UNREACHABLE && passable(neighbour) {
+                distance[neighbour] = next;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Walls all around but for the given edge tiles.
+    fn walled(open: &[Tile]) -> Terrain {
+        Terrain::new(|t| {
+            if is_edge(t) && !open.contains(&t) {
+                Cell::Wall
+            } else {
+                Cell::Plain
+            }
+        })
+    }
+
+    #[test]
+    fn flood_fill_counts_diagonal_steps_as_one() {
+        let distance = flood_fill(&[(10, 10)], |_| true);
+        assert_eq!(distance[(10, 10)], 0);
+        assert_eq!(distance[(13, 12)], 3);
+        assert_eq!(distance[(0, 49)], 39);
+    }
+
+    #[test]
+    fn flood_fill_goes_around_walls() {
+        // A wall down x = 20 with a gap at y = 40
+        let terrain = Terrain::new(|(x, y)| {
+            if x == 20 && y != 40 {
+                Cell::Wall
+            } else {
+                Cell::Plain
+            }
+        });
+        let distance = terrain.flood_fill(&[(10, 10)], |_| true);
+        assert_eq!(distance[(20, 40)], 30);
+        assert_eq!(distance[(30, 10)], 60);
+        assert_eq!(distance[(20, 10)], UNREACHABLE);
+
+        let blocked = terrain.flood_fill(&[(10, 10)], |t| t != (20, 40));
+        assert_eq!(blocked[(30, 10)], UNREACHABLE);
+    }
+
+    #[test]
+    fn distance_transform_measures_to_nearest_wall() {
+        let terrain = Terrain::new(|(x, y)| {
+            if is_edge((x, y)) || (x, y) == (10, 10) {
+                Cell::Wall
+            } else {
+                Cell::Plain
+            }
+        });
+        let distance = terrain.distance_transform();
+        assert_eq!(distance[(0, 0)], 0);
+        assert_eq!(distance[(10, 10)], 0);
+        assert_eq!(distance[(1, 1)], 1);
+        assert_eq!(distance[(12, 13)], 3);
+        assert_eq!(distance[(25, 25)], 15);
+        assert_eq!(distance[(40, 40)], 9);
+    }
+
+    #[test]
+    fn exits_are_split_by_walls() {
+        let mut open = (10..15).map(|x| (x, 0)).collect::<Vec<_>>();
+        open.extend(&[(0, 20), (0, 21), (0, 30)]);
+        let exits = walled(&open).exits();
+
+        let found = exits
+            .iter()
+            .map(|e| (e.side, e.tiles.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (Side::Top, open[..5].to_vec()),
+                (Side::Left, vec![(0, 20), (0, 21)]),
+                (Side::Left, vec![(0, 30)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_reads_fixtures() {
+        let mut fixture = vec![".".repeat(ROOM_SIZE); ROOM_SIZE];
+        fixture[3] = format!("#~{}", ".".repeat(ROOM_SIZE - 2));
+        let terrain = Terrain::parse(&fixture.join("\n")).unwrap();
+        assert_eq!(terrain.get((0, 3)), Cell::Wall);
+        assert_eq!(terrain.get((1, 3)), Cell::Swamp);
+        assert_eq!(terrain.get((2, 3)), Cell::Plain);
+
+        assert!(Terrain::parse("#").is_none());
+    }
+
+    #[test]
+    fn chokepoints_are_gaps_that_cannot_be_walked_around() {
+        // A wall across the room with a gap of two at x = 20, and a gap of
+        // two between pillars at y = 10 that's easily walked around
+        let terrain = Terrain::parse(
+            "
+        ##################################################
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #.........#..#...................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        ####################..############################
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        #................................................#
+        ##################################################
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(terrain.chokepoints(3), vec![vec![(20, 25), (21, 25)]]);
+        assert!(terrain.chokepoints(1).is_empty());
+    }
+}
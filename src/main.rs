@@ -1,8 +1,16 @@
 use lib::{
-    config::Config, controllers::MemoryController, creeps::Creep, error::Res, logging,
-    mayor::Mayor, rooms::Room,
+    config::Config,
+    controllers::{FaultController, MemoryController},
+    empire::Empire,
+    error::Res,
+    intel::Intel,
+    logging,
+    mayor::Mayor,
+    registry::{creep_entity, room_entity, Registry},
+    rooms::RoomKind,
 };
 use log::*;
+use std::error::Error;
 use stdweb::js;
 
 fn main() {
@@ -34,9 +42,11 @@ fn game_loop_catch() {
     match game_loop() {
         Ok(()) => {}
         Err(e) => {
-            warn!("{}", e.description());
-            if let Some(s) = e.source() {
+            warn!("{}", e);
+            let mut source = e.source();
+            while let Some(s) = source {
                 warn!("cause: {}", s);
+                source = s.source();
             }
         }
     }
@@ -48,13 +58,53 @@ fn game_loop() -> Res<()> {
 
     let config = Config::new()?;
 
+    let mut faults = FaultController::new()?;
+    let mut registry = Registry::load(&mut faults);
+    // Kept until the end of the tick, so the Mayors can confirm the
+    // directives they follow before it saves its memory
+    let mut empire = faults.guard("empire", || Empire::new(&config));
+    let mut directives = empire
+        .as_mut()
+        .and_then(|empire| faults.guard("empire", || empire.run(&mut registry)))
+        .unwrap_or_default();
+
     for room in screeps::game::rooms::values() {
-        Mayor::new(Room::new(room)?, &config).run()?;
+        let name = room.name();
+        match RoomKind::of(&room, &config) {
+            RoomKind::Owned => {
+                let room_directives = directives.remove(&name).unwrap_or_default();
+                if let Some((room, mut creeps)) = registry.room_and_creeps(name) {
+                    let followed = faults.guard(&room_entity(name), || {
+                        Mayor::new(room, &config, room_directives)
+                            .run(&mut creeps)
+                            .map_err(|e| e.with_room(&name.to_string()))
+                    });
+                    if let (Some(empire), Some(followed)) = (empire.as_mut(), followed) {
+                        for directive in followed {
+                            empire.confirm(directive);
+                        }
+                    }
+                }
+            }
+            kind => {
+                faults.guard(&room_entity(name), || {
+                    Intel::new(room, kind)
+                        .run()
+                        .map_err(|e| e.with_room(&name.to_string()))
+                });
+            }
+        }
     }
 
-    for creep in screeps::game::creeps::values() {
-        Creep::new(creep)?.run()?;
+    for creep in registry.creeps_mut() {
+        let name = creep.obj().name();
+        faults.guard(&creep_entity(&name), || {
+            creep.run().map_err(|e| e.with_creep(&name))
+        });
     }
 
+    registry.save();
+    faults.summary();
+
     Ok(())
 }